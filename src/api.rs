@@ -1,15 +1,23 @@
 use std::env;
-use crate::args::CLICommands;
+use crate::dimension::{AspectRatio, Resolution};
+use crate::explore::Explorer;
+use crate::flags::{Category, Purity};
+use crate::pager::SearchPager;
+use crate::params::{encode_segment, CollectionParams, SearchParams};
+use crate::retry::{RateLimiter, RetryPolicy, DEFAULT_RATE_LIMIT};
 
 const ENV_API_KEY: &str = "WALLHAVEN_API_KEY";
+const ENV_BASE_URL: &str = "WALLHAVEN_BASE_URL";
+const ENV_IMAGE_HOST: &str = "WALLHAVEN_IMAGE_HOST";
+/// Default api base url
 pub const BASE_URL: &str = "https://wallhaven.cc/api/v1";
 
 
 pub fn get_key() -> Result<String, String> {
     let v = env::var(ENV_API_KEY)
-        .map_err(|_| format!("Cannot read API key"))?;
+        .map_err(|_| String::from("Cannot read API key"))?;
 
-    return Ok(v);
+    Ok(v)
 }
 
 
@@ -24,44 +32,55 @@ pub struct SearchResponse {
     #[serde(rename = "meta")]
     pub meta: WallpaperMeta,
 }
+impl SearchResponse {
+    /// Append the wallpapers of a following page, keeping its meta
+    pub fn merge(&mut self, other: SearchResponse) {
+        self.data.extend(other.data);
+        self.meta = other.meta;
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
 pub struct Wallpaper {
-    id: String,
-    url: String,
-    short_url: String,
-    views: i32,
-    favorites: i32,
-    source: String,
-    purity: String,
-    category: String,
-    dimension_x: i32,
-    dimension_y: i32,
-    resolution: String,
-    ratio: String,
-    file_size: i32,
-    file_type: String,
-    created_at: String,
-    colors: Vec<String>,
-    path: String,
-    thumbs: Thumbs,
+    pub id: String,
+    pub url: String,
+    pub short_url: String,
+    pub views: i32,
+    pub favorites: i32,
+    pub source: String,
+    pub purity: Purity,
+    pub category: Category,
+    pub dimension_x: i32,
+    pub dimension_y: i32,
+    pub resolution: Resolution,
+    #[serde(with = "crate::dimension::as_decimal")]
+    pub ratio: AspectRatio,
+    pub file_size: i32,
+    pub file_type: String,
+    pub created_at: String,
+    pub colors: Vec<String>,
+    pub path: String,
+    pub thumbs: Thumbs,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Thumbs {
-    large: String,
-    original: String,
-    small: String,
+    pub large: String,
+    pub original: String,
+    pub small: String,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
 pub struct WallpaperMeta {
-    current_page: i32,
-    last_page: i32,
+    pub current_page: i32,
+    pub last_page: i32,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    per_page: i32, //Should be int,idk why its string even when api guide defines as int
-    total: i32,
-    query: MetaQuery,
-    seed: Option<String>,
+    pub per_page: i32, //Should be int,idk why its string even when api guide defines as int
+    pub total: i32,
+    // Collections meta has no query
+    #[serde(default)]
+    pub query: Option<MetaQuery>,
+    pub seed: Option<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
@@ -88,12 +107,13 @@ pub struct WallpaperInfo {
     pub views: i32,
     pub favorites: i32,
     pub source: String,
-    pub purity: String,
-    pub category: String,
+    pub purity: Purity,
+    pub category: Category,
     pub dimension_x: i32,
     pub dimension_y: i32,
-    pub resolution: String,
-    pub ratio: String,
+    pub resolution: Resolution,
+    #[serde(with = "crate::dimension::as_decimal")]
+    pub ratio: AspectRatio,
     pub file_size: i32,
     pub file_type: String,
     pub created_at: String,
@@ -147,15 +167,17 @@ pub struct UserSettingsResponse {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
 pub struct UserSettings {
-    thumb_size: String,
-    per_page: String,
-    purity: Vec<String>,
-    categories: Vec<String>,
-    resolutions: Vec<String>,
-    aspect_ratios: Vec<String>,
-    toplist_range: String,
-    tag_blacklist: Vec<String>,
-    user_blacklist: Vec<String>,
+    pub thumb_size: String,
+    pub per_page: String,
+    #[serde(with = "crate::flags::as_list")]
+    pub purity: Purity,
+    #[serde(with = "crate::flags::as_list")]
+    pub categories: Category,
+    pub resolutions: Vec<Resolution>,
+    pub aspect_ratios: Vec<AspectRatio>,
+    pub toplist_range: String,
+    pub tag_blacklist: Vec<String>,
+    pub user_blacklist: Vec<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
@@ -166,17 +188,17 @@ pub struct UserCollectionsResponse {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
 pub struct UserCollections {
-    id: i32,
-    label: String,
-    views: i32,
-    public: i32,
-    count: i32,
+    pub id: i32,
+    pub label: String,
+    pub views: i32,
+    pub public: i32,
+    pub count: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
 pub struct ErrorResponse {
-    error: String,
+    pub error: String,
 }
 
 
@@ -186,28 +208,60 @@ pub(crate) trait Url {
 }
 
 
-use futures::TryFutureExt;
-use futures::stream::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
-
 #[derive(Debug)]
 pub enum WallhavenClientError {
     RequestError(String),
     DecodeError(String),
     WriteError(String),
+    /// Local files that can't be read, ex. a checkpoint or the directory to verify
+    ReadError(String),
+    /// Files found by `verify` that aren't the expected images
+    VerifyFailed(String),
+    /// Missing or invalid API key (401)
+    Unauthorized,
+    /// Requested resource doesn't exist (404)
+    NotFound,
+    /// Too many requests (429), `retry_after` seconds as sent by the server
+    RateLimited { retry_after: Option<u64> },
+    /// Any other non successful status
+    Api { status: u16, message: String },
+    /// Stopped by the user
+    Interrupted,
 }
 
 impl std::fmt::Display for WallhavenClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Unauthorized => {
+                write!(f, "Unauthorized - missing or invalid API key")
+            },
+            Self::NotFound => {
+                write!(f, "Not Found")
+            },
+            Self::RateLimited { retry_after: Some(secs) } => {
+                write!(f, "Rate Limited - retry after {} seconds", secs)
+            },
+            Self::RateLimited { retry_after: None } => {
+                write!(f, "Rate Limited")
+            },
+            Self::Api { status, message } => {
+                write!(f, "Api Error {} - {}", status, message)
+            },
+            Self::Interrupted => {
+                write!(f, "Interrupted")
+            },
             Self::DecodeError(e) => {
                 write!(f, "Decode Error - {}", e)
             },
             Self::WriteError(e) => {
                 write!(f, "Write Error - {}", e)
             },
+            Self::ReadError(e) => {
+                write!(f, "Read Error - {}", e)
+            },
+            Self::VerifyFailed(e) => {
+                write!(f, "Verify Failed - {}", e)
+            },
             Self::RequestError(e) => {
                 write!(f, "Request Error - {}", e)
             },
@@ -220,152 +274,210 @@ impl std::error::Error for WallhavenClientError {}
 
 #[derive(Debug)]
 pub struct WallhavenClient {
-    http_client: reqwest::Client,
-    commands: CLICommands,
+    pub(crate) http_client: reqwest::Client,
+    base_url: String,
+    image_host: Option<String>,
+    limiter: RateLimiter,
+    pub(crate) api_retry: RetryPolicy,
+    pub(crate) download_retry: RetryPolicy,
 }
 
 impl WallhavenClient {
-    pub fn new(commands: CLICommands) -> Result<Self, String> {
-        let api_key = get_key();
-
+    /// Create a client, authenticating every request with `api_key` if given
+    pub fn new(api_key: Option<&str>) -> Result<Self, WallhavenClientError> {
         /* Create http client */
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/json"));
         headers.insert(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/json"));
-        if let Ok(k) = api_key {
-            let header_api_value = reqwest::header::HeaderValue::from_str(&k).map_err(|_| String::from("header error"))?;
+        if let Some(k) = api_key {
+            let header_api_value = reqwest::header::HeaderValue::from_str(k)
+                .map_err(|_| WallhavenClientError::RequestError(String::from("Invalid API key header value")))?;
             headers.insert("X-API-KEY", header_api_value);
         }
 
         let client = reqwest::ClientBuilder::new()
             .default_headers(headers)
             .build()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
 
         Ok(
             Self {
                 http_client: client,
-                commands,
+                base_url: String::from(BASE_URL),
+                image_host: None,
+                limiter: RateLimiter::new(DEFAULT_RATE_LIMIT),
+                api_retry: RetryPolicy::default(),
+                download_retry: RetryPolicy::default(),
             }
         )
     }
 
-    pub async fn execute(&self) -> Result<String, WallhavenClientError> {
-        let resp = match &self.commands {
-            CLICommands::Search(s) => {
-                let res = self.request(s.to_url(BASE_URL))
-                    .await?;
-
-                // Check if we got bad status response and return it
-                if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    let error_response = serde_json::to_string(&r)
-                            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                    return Ok(error_response);
-                }
+    /// Create a client configured from the environment
+    ///
+    /// `WALLHAVEN_API_KEY` - API key
+    /// and the urls of [`WallhavenClient::with_env`]
+    pub fn from_env() -> Result<Self, WallhavenClientError> {
+        Ok(Self::new(get_key().ok().as_deref())?.with_env())
+    }
 
-                // Check if response has the structure as described in api guide
-                let searchresp: SearchResponse = serde_json::from_str(&res)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-
-
-                //download wallpapers
-                if let Some(path) = s.path.clone() {
-                    for w in searchresp.data {
-                        let path = path.clone();
-                        let wallpaper_path = std::path::PathBuf::from(path);
-                        // Should I do a safe check, even if I know the api wallpaper response
-                        // has such format?
-                        if let Some(image_name) = w.path.split("/").last() {
-                            let file_path = wallpaper_path.join(image_name);
-
-                            self.download_image(&w.path, &file_path).await?
-                        }
-                    }
-
-                    format!("")
-                } else {
-                    serde_json::to_string(&searchresp)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?
-                }
-            }, 
-            CLICommands::WallpaperInfo(w) => {
-                let res = self.request(w.to_url(BASE_URL))
-                    .await?;
-
-                if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    let error_response = serde_json::to_string(&r)
-                            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                    return Ok(error_response);
-                }
+    /// Take the urls from the environment, if set
+    ///
+    /// `WALLHAVEN_BASE_URL` - api base url
+    /// `WALLHAVEN_IMAGE_HOST` - image host, see [`WallhavenClient::with_image_host`]
+    pub fn with_env(mut self) -> Self {
+        if let Ok(base_url) = env::var(ENV_BASE_URL) {
+            self = self.with_base_url(&base_url);
+        }
+        if let Ok(image_host) = env::var(ENV_IMAGE_HOST) {
+            self = self.with_image_host(&image_host);
+        }
 
-                // Parse to data structure to check it keeps the same structure as described in api
-                // guide
-                let wallpaperinfo: WallpaperInfoResponse = serde_json::from_str(&res)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+        self
+    }
 
-                serde_json::to_string(&wallpaperinfo)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?
-            },
-            CLICommands::TagInfo(t) => {
-                let res = self.request(t.to_url(BASE_URL))
-                    .await?;
-
-                if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    let error_response = serde_json::to_string(&r)
-                            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                    return Ok(error_response);
-                }
+    /// Use `base_url` instead of [`BASE_URL`] for api requests
+    /// Ex. http://localhost:8080/api/v1
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = String::from(base_url.trim_end_matches('/'));
+        self
+    }
 
-                let taginfo: TagResponse = serde_json::from_str(&res)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+    /// Download images from `image_host` instead of the host in the wallpaper urls,
+    /// keeping the url path
+    /// Ex. http://localhost:8080 turns https://w.wallhaven.cc/full/ab/wallhaven-abcd12.jpg
+    /// into http://localhost:8080/full/ab/wallhaven-abcd12.jpg
+    pub fn with_image_host(mut self, image_host: &str) -> Self {
+        self.image_host = Some(String::from(image_host.trim_end_matches('/')));
+        self
+    }
 
-                serde_json::to_string(&taginfo)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?
-            },
-            CLICommands::UserSettings(us) => {
-                let res = self.request(us.to_url(BASE_URL))
-                    .await?;
-
-                if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    let error_response = serde_json::to_string(&r)
-                            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                    return Ok(error_response);
-                }
+    /// Allow at most `per_minute` api requests per minute, 0 disables the limit
+    /// Defaults to [`DEFAULT_RATE_LIMIT`]
+    pub fn with_rate_limit(mut self, per_minute: u32) -> Self {
+        self.limiter = RateLimiter::new(per_minute);
+        self
+    }
+
+    /// Retry policy of api requests
+    pub fn with_api_retry(mut self, retry: RetryPolicy) -> Self {
+        self.api_retry = retry;
+        self
+    }
 
-                let usersettings: UserSettingsResponse = serde_json::from_str(&res)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+    /// Retry policy of image downloads
+    pub fn with_download_retry(mut self, retry: RetryPolicy) -> Self {
+        self.download_retry = retry;
+        self
+    }
 
-                serde_json::to_string(&usersettings)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?
-            },
-            CLICommands::UserCollections(uc) => {
-                let res = self.request(uc.to_url(BASE_URL))
-                    .await?;
-
-                if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    let error_response = serde_json::to_string(&r)
-                            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                    return Ok(error_response);
+    /// Url an image is downloaded from, after the image host rewrite
+    pub fn image_url(&self, url: &str) -> String {
+        let Some(host) = &self.image_host else {
+            return String::from(url);
+        };
+
+        match reqwest::Url::parse(url) {
+            Ok(u) => {
+                let mut rewritten = format!("{host}{}", u.path());
+                if let Some(q) = u.query() {
+                    rewritten.push('?');
+                    rewritten.push_str(q);
                 }
+                rewritten
+            },
+            // Relative url, only prefix the host
+            Err(_) => format!("{host}/{}", url.trim_start_matches('/')),
+        }
+    }
 
-                let usercollections: UserCollectionsResponse = serde_json::from_str(&res)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+    /// Search wallpapers
+    pub async fn search(&self, params: &SearchParams) -> Result<SearchResponse, WallhavenClientError> {
+        self.get(params.to_url(&self.base_url)).await
+    }
 
+    /// Walk the pages of a search starting at `params.page`
+    ///
+    /// Stops after `pages` pages (all of them if `None`), after `limit` wallpapers
+    /// or on the last page, whatever comes first.
+    pub fn search_pages(&self, params: &SearchParams, pages: Option<u32>, limit: Option<usize>) -> SearchPager<'_> {
+        SearchPager::search(self, params, pages, limit)
+    }
 
-                serde_json::to_string(&usercollections)
-                    .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?
-            }
+    /// Crawl wallpapers similar to wallpaper `id`, breadth first
+    ///
+    /// Follows `like:` searches with the preferences of `params` up to `depth` levels,
+    /// keeping the first `fan_out` new wallpapers of each search, out of its first page of results.
+    pub fn explore(&self, id: &str, params: &SearchParams, depth: u32, fan_out: usize) -> Explorer<'_> {
+        Explorer::new(self, id, params, depth, fan_out)
+    }
+
+    /// Get wallpaper information by id
+    pub async fn wallpaper(&self, id: &str) -> Result<WallpaperInfo, WallhavenClientError> {
+        let res: WallpaperInfoResponse = self.get(format!("{}/w/{}", self.base_url, id)).await?;
+
+        Ok(res.data)
+    }
+
+    /// Get tag information by id
+    pub async fn tag(&self, id: i32) -> Result<Tag, WallhavenClientError> {
+        let res: TagResponse = self.get(format!("{}/tag/{}", self.base_url, id)).await?;
+
+        Ok(res.data)
+    }
+
+    /// Get the settings of the api key account
+    pub async fn settings(&self) -> Result<UserSettings, WallhavenClientError> {
+        let res: UserSettingsResponse = self.get(format!("{}/settings", self.base_url)).await?;
+
+        Ok(res.data)
+    }
+
+    /// Get the public collections of `user`, or all the api key account collections if `None`
+    pub async fn collections(&self, user: Option<&str>) -> Result<Vec<UserCollections>, WallhavenClientError> {
+        let url = match user {
+            Some(username) => format!("{}/collections/{}", self.base_url, encode_segment(username)),
+            None => format!("{}/collections", self.base_url),
         };
+        let res: UserCollectionsResponse = self.get(url).await?;
+
+        Ok(res.data)
+    }
 
-        Ok(resp)
+    /// Get a page of the wallpapers in a collection
+    pub async fn collection(&self, params: &CollectionParams) -> Result<SearchResponse, WallhavenClientError> {
+        self.get(params.to_url(&self.base_url)).await
     }
 
+    /// Walk the pages of a collection starting at `params.page`,
+    /// same as [`WallhavenClient::search_pages`]
+    pub fn collection_pages(&self, params: &CollectionParams, pages: Option<u32>, limit: Option<usize>) -> SearchPager<'_> {
+        SearchPager::collection(self, params, pages, limit)
+    }
+
+    /// Request `url` and decode the response body as `T`
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: String) -> Result<T, WallhavenClientError> {
+        let res = self.request(url).await?;
+
+        // Check if response has the structure as described in api guide
+        serde_json::from_str(&res)
+            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))
+    }
+
+    /// Request `url` going through the rate limiter, retrying failures
     pub async fn request(&self, url: String) -> Result<String, WallhavenClientError> {
+        self.api_retry.run(|| async {
+            self.limiter.acquire().await;
+            self.request_once(&url).await
+        }).await
+    }
+
+    async fn request_once(&self, url: &str) -> Result<String, WallhavenClientError> {
         let response = self.http_client
             .get(url)
             .send()
             .await
             .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
+        let response = check_status(response).await?;
 
         match response.text().await {
             Ok(r) => {
@@ -379,65 +491,92 @@ impl WallhavenClient {
         }
 
     }
+}
 
-    pub async fn download_image(&self, url: &str, path: &std::path::PathBuf) -> Result<(), WallhavenClientError> {
-        // Reqwest setup
-        let res = self.http_client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
 
-        //println!("{:#?}", res);
-
-        // Get information for bar
-        let total_size = res
-            .content_length()
-            .ok_or(format!("Failed to get content length from '{}'", &url))
-            .map_err(|e| WallhavenClientError::RequestError(e))?;
-
-        // Indicatif setup
-        let pb = ProgressBar::new(total_size);
-        let style = ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-            .unwrap()
-            .progress_chars("#>-");
-        pb.set_style(style);
-        pb.set_message(format!("Downloading {}", url));
-
-        // Create file path
-        let file_path = std::path::Path::new(path);
-        let mut file = OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(file_path)
-            .await
-            .map_err(|e| WallhavenClientError::WriteError(
-                    format!("Failed to create file - {}", e.to_string()))
-                )?;
+/// Map a non successful response to its `WallhavenClientError`
+pub(crate) async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, WallhavenClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
 
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED => Err(WallhavenClientError::Unauthorized),
+        reqwest::StatusCode::NOT_FOUND => Err(WallhavenClientError::NotFound),
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = response.headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok());
+
+            Err(WallhavenClientError::RateLimited { retry_after })
+        },
+        _ => {
+            let body = response.text().await.unwrap_or_default();
+            // Api errors come as {"error": "..."}, fallback to raw body otherwise
+            let message = match serde_json::from_str::<ErrorResponse>(&body) {
+                Ok(r) => r.error,
+                Err(_) => body,
+            };
+
+            Err(WallhavenClientError::Api { status: status.as_u16(), message })
+        }
+    }
+}
 
-        // Write file
-        let mut downloaded: u64 = 0;
-        let mut stream = res.bytes_stream();
 
-        while let Some(item) = stream.next().await {
-            let chunk = item
-                .or(Err(
-                    WallhavenClientError::RequestError(format!("Error while downloading file"))
-                    ))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
 
-            file.write(&chunk)
-                .map_err(|e| WallhavenClientError::WriteError(format!("Error while writing to file - {}", e.to_string())))
-                .await?;
+    #[test]
+    fn rewrites_image_hosts() {
+        let url = "https://w.wallhaven.cc/full/94/wallhaven-94x38z.jpg";
+        let client = WallhavenClient::new(None).unwrap();
+        assert_eq!(client.image_url(url), url);
 
-            let new = u64::min(downloaded + (chunk.len() as u64), total_size);
-            downloaded = new;
-            pb.set_position(new);
-        }
+        let client = client.with_image_host("http://mirror.lan/");
+        assert_eq!(client.image_url(url), "http://mirror.lan/full/94/wallhaven-94x38z.jpg");
+        assert_eq!(client.image_url("https://th.wallhaven.cc/small/94/94x38z.jpg?v=2"), "http://mirror.lan/small/94/94x38z.jpg?v=2");
+        assert_eq!(client.image_url("/full/94/wallhaven-94x38z.jpg"), "http://mirror.lan/full/94/wallhaven-94x38z.jpg");
+        assert_eq!(client.image_url("full/94/wallhaven-94x38z.jpg"), "http://mirror.lan/full/94/wallhaven-94x38z.jpg");
+    }
+
+    #[tokio::test]
+    async fn encodes_usernames_in_paths() {
+        let (url, requests) = fixtures::serve(|_| (200, Vec::new(), br#"{"data": []}"#.to_vec())).await;
+        let client = WallhavenClient::new(None).unwrap().with_base_url(&url).with_rate_limit(0);
 
-        pb.finish_with_message(format!("Downloaded {}", url));
+        client.collections(Some("some user?#/x")).await.unwrap();
+        assert_eq!(requests.lock().unwrap()[0].path, "/collections/some%20user%3F%23%2Fx");
+    }
+
+    #[tokio::test]
+    async fn maps_statuses_to_errors() {
+        let (url, _) = fixtures::serve(|req| match req.path.as_str() {
+            "/401" => (401, Vec::new(), Vec::new()),
+            "/404" => (404, Vec::new(), br#"{"error": "Nothing here"}"#.to_vec()),
+            "/429" => (429, vec![("Retry-After", String::from(" 7 "))], Vec::new()),
+            "/429-date" => (429, vec![("Retry-After", String::from("Wed, 21 Oct 2026 07:28:00 GMT"))], Vec::new()),
+            "/503" => (503, Vec::new(), br#"{"error": "Down for maintenance"}"#.to_vec()),
+            "/500" => (500, Vec::new(), b"oops".to_vec()),
+            _ => (200, Vec::new(), b"{}".to_vec()),
+        }).await;
+        let client = WallhavenClient::new(None).unwrap();
+        let status = |path: &'static str| {
+            let req = client.http_client.get(format!("{url}{path}"));
+            async move { check_status(req.send().await.unwrap()).await.map(|r| r.status().as_u16()) }
+        };
 
-        Ok(())
+        assert!(matches!(status("/200").await, Ok(200)));
+        assert!(matches!(status("/401").await, Err(WallhavenClientError::Unauthorized)));
+        assert!(matches!(status("/404").await, Err(WallhavenClientError::NotFound)));
+        assert!(matches!(status("/429").await, Err(WallhavenClientError::RateLimited { retry_after: Some(7) })));
+        // Only seconds are understood
+        assert!(matches!(status("/429-date").await, Err(WallhavenClientError::RateLimited { retry_after: None })));
+        assert!(matches!(status("/503").await, Err(WallhavenClientError::Api { status: 503, message }) if message == "Down for maintenance"));
+        assert!(matches!(status("/500").await, Err(WallhavenClientError::Api { status: 500, message }) if message == "oops"));
     }
 }
@@ -1,7 +1,8 @@
-use std::str::FromStr;
+use std::path::PathBuf;
 
-use crate::api::Url;
 use clap::{Args, Parser, Subcommand, ArgGroup};
+use serde::Serialize;
+use wallhaven::{Category, CollectionParams, Color, ExistsPolicy, FileNamer, Filter, Purity, RatioFilter, Resolution, SearchParams, SearchQuery, Seed, Template, Variant};
 
 #[derive(Debug, Parser)]
 #[clap(
@@ -14,12 +15,90 @@ to preferences, with optional use of API key to use account preferences and acce
 pub struct CLIArgs {
     #[clap(subcommand)]
     pub commands: CLICommands,
+
+    /// Config file
+    ///
+    ///    Defaults to ~/.config/wallhaven/config.toml
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONFIG"),
+    )]
+    pub config: Option<String>,
+
+    /// Profile
+    ///
+    ///    Use the values of a [profile.<name>] config table
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONFIG"),
+    )]
+    pub profile: Option<String>,
+
+    /// Api base url
+    ///
+    ///    Overrides WALLHAVEN_BASE_URL
+    ///    Ex. http://localhost:8080/api/v1
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONNECTION"),
+    )]
+    pub base_url: Option<String>,
+
+    /// Image host
+    ///
+    ///    Download images from this host instead, keeping the image path
+    ///    Overrides WALLHAVEN_IMAGE_HOST
+    ///    Ex. http://localhost:8080
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONNECTION"),
+    )]
+    pub image_host: Option<String>,
+
+    /// Rate limit
+    ///
+    ///    Maximum api requests per minute, 0 disables the limit
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONNECTION"),
+           default_value_t = wallhaven::retry::DEFAULT_RATE_LIMIT,
+    )]
+    pub rate_limit: u32,
+
+    /// Retries
+    ///
+    ///    Times a failed api request is retried
+    ///    (rate limited, server or connection errors)
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONNECTION"),
+           default_value_t = 3,
+    )]
+    pub retries: u32,
+
+    /// Download retries
+    ///
+    ///    Times a failed download is retried, resuming where it stopped
+    #[clap(long,
+           global = true,
+           verbatim_doc_comment,
+           help_heading = Some("CONNECTION"),
+           default_value_t = 3,
+    )]
+    pub download_retries: u32,
 }
 
 #[derive(Debug, Subcommand)]
 //#[derive(Debug, Subcommand, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum CLICommands {
-    /// Search wallpaper by query or colors
+    /// Search wallpaper by query and/or colors
     Search(SearchArgs),
     /// Get wallpaper info
     WallpaperInfo(WallpaperInfoArgs),
@@ -29,23 +108,27 @@ pub enum CLICommands {
     UserSettings(UserSettingsArgs),
     /// Show user collections
     UserCollections(UserCollectionsArgs),
+    /// List or download the wallpapers of a collection
+    Collection(CollectionArgs),
+    /// Manage and run named saved searches
+    Saved(SavedArgs),
+    /// Crawl wallpapers similar to a wallpaper, following like: searches
+    Explore(ExploreArgs),
+    /// Check downloaded wallpapers, reporting or fetching again the bad ones
+    Verify(VerifyArgs),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Args, Serialize)]
 #[clap(group(
             ArgGroup::new("search_method")
                 .required(true)
-                .multiple(false)
+                .multiple(true)
                 .args(&["query", "colors"]),
         ))]
 pub struct SearchArgs {
-    /// Path to save wallpapers
-    #[clap(short = 'S',
-           long,
-           verbatim_doc_comment,
-           help_heading = "DOWNLOAD"
-    )]
-    pub path: Option<String>,
+    #[clap(flatten)]
+    #[serde(flatten)]
+    pub download: DownloadArgs,
 
     /// Query string
     ///
@@ -53,6 +136,7 @@ pub struct SearchArgs {
     ///    -tagname - exclude a tag/keyword
     ///    +tag1 +tag2 - must have tag1 and tag2
     ///    +tag1 -tag2 - must have tag1 and NOT tag2
+    ///    +"tag name" - tags with spaces are quoted
     ///    @username - user uploads
     ///    id:123 - Exact tag search (can not be combined)
     ///    type:{png/jpg} - Search for file type (jpg = jpeg)
@@ -72,28 +156,34 @@ pub struct SearchArgs {
     ///
     ///    Turn categories on(1) or off(0)
     ///    (general/anime/people).
+    ///    Or list them by name
+    ///
+    ///    Ex. 010, anime,people
     #[clap(short = 'c',
            long,
            verbatim_doc_comment,
            help_heading = Some("SEARCH PREFERENCES"),
            //default_value = "111",
-           value_parser = ["100", "101", "110", "111"],
+           value_parser = clap::value_parser!(Category),
     )]
-    categories: Option<String>,
+    categories: Option<Category>,
 
     /// Purity
     ///
     ///    Turn purities on(1) or off(0)
     ///    *NSFW requires a valid API key*
     ///    (sfw/sketchy/nsfw).
+    ///    Or list them by name
+    ///
+    ///    Ex. 011, sfw,sketchy
     #[clap(short = 'p',
            long,
            verbatim_doc_comment,
            help_heading = Some("SEARCH PREFERENCES"),
            //default_value = "100",
-           value_parser = ["100", "101", "110", "111"],
+           value_parser = clap::value_parser!(Purity),
     )]
-    purity: Option<String>,
+    purity: Option<Purity>,
 
     /// Sorting
     ///
@@ -141,51 +231,219 @@ pub struct SearchArgs {
            verbatim_doc_comment,
            help_heading = Some("WALLPAPER PREFERENCES"),
            //default_value = "",
+           value_parser = clap::value_parser!(Resolution),
     )]
-    atleast: Option<String>,
+    atleast: Option<Resolution>,
 
     /// Resolutions
     ///
     ///    List of exact wallpaper resolutions
-    ///    Single resolution allowed.
+    ///
+    ///    Ex. 1920x1080,1920x1200
     #[clap(short = 'r',
            long,
            verbatim_doc_comment,
            help_heading = Some("WALLPAPER PREFERENCES"),
            //default_value = "1920x1080,1920x1200",
+           value_delimiter = ',',
+           value_parser = clap::value_parser!(Resolution),
     )]
-    resolutions: Option<String>,
+    resolutions: Vec<Resolution>,
 
     /// Ratios
     ///
-    ///    List of aspect ratios
-    ///    Single ratio allowed.
+    ///    List of aspect ratios (WxH or W:H)
+    ///    or landscape, portrait, ultrawide
     ///
-    ///    Ex. 16x9,16x10
+    ///    Ex. 16x9,16:10
+    ///        ultrawide
     #[clap(short = 'R',
            long,
            verbatim_doc_comment,
            help_heading = Some("WALLPAPER PREFERENCES"),
            //default_value = "16x9,16x10",
+           value_delimiter = ',',
+           value_parser = clap::value_parser!(RatioFilter),
     )]
-    ratios: Option<String>,
+    ratios: Vec<RatioFilter>,
 
-    /// Color
+    /// Colors
     ///
-    ///    Search by hex color
+    ///    Search by hex colors or CSS color names,
+    ///    snapped to the nearest color wallhaven indexes
     ///    Ex.  --colors 0066cc
-    ///         --colors #333393
+    ///         --colors #333393,teal
     #[clap(short = 'C',
            long,
            verbatim_doc_comment,
            help_heading = Some("SEARCH"),
-           //required_unless_present_any = ["query"],
-           //conflicts_with = "query",
-           //default_value = "000000",
-           value_parser = valid_color
+           value_delimiter = ',',
+           value_parser = clap::value_parser!(Color),
+    )]
+    colors: Vec<Color>,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    pub paging: PagingArgs,
+
+    /// Since last run
+    ///
+    ///    Only wallpapers newer than the last run with this key,
+    ///    paging goes on until the first one already seen
+    ///    The newest wallpaper of each key is kept in
+    ///    ~/.local/state/wallhaven/state.toml
+    ///    Can't be cut short by --page, --pages or --limit
+    ///
+    ///    Ex. --since-last-run latest-anime
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           conflicts_with_all = &["page", "pages", "limit"],
     )]
-    colors: Option<String>,
+    pub since_last_run: Option<String>,
 
+    /// Seed
+    ///
+    ///     Optional seed for random results
+    ///     [a-zA-Z0-9]{6}
+    ///     Generated when not given, printed to stderr
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           //default_value_t = 1,
+           value_parser = clap::value_parser!(Seed),
+    )]
+    seed: Option<Seed>,
+}
+
+
+#[derive(Debug, Args, Serialize)]
+pub struct DownloadArgs {
+    /// Path to save wallpapers
+    #[clap(short = 'S',
+           long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD"
+    )]
+    pub path: Option<String>,
+
+    /// Jobs
+    ///
+    ///    Number of wallpapers to download at the same time
+    #[clap(short = 'j',
+           long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+           default_value_t = 1,
+           value_parser = clap::value_parser!(u16).range(1..),
+    )]
+    pub jobs: u16,
+
+    /// On exists
+    ///
+    ///    What to do when a wallpaper file already exists
+    ///    skip - keep the existing file
+    ///    overwrite - download it again
+    ///    rename - download to <name>-<n>.<ext>
+    ///    verify - download again only if it isn't the expected image
+    ///             (size, type or truncated)
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+           ignore_case = true,
+           default_value = "skip",
+           value_parser = ["skip", "overwrite", "rename", "verify"],
+    )]
+    on_exists: String,
+
+    /// Name template
+    ///
+    ///    File name of each wallpaper, from its fields
+    ///    {id} {resolution} {category} {purity} {ratio} {views}
+    ///    {favorites} {file_size} {file_type} {colors} {ext} ...
+    ///    {created_at:%Y%m%d} - date format (%Y %y %m %d %H %M %S)
+    ///    Unsafe characters in values are replaced by _,
+    ///    names repeated in a run get -<id> appended
+    ///    Defaults to wallhaven-{id}.{ext},
+    ///    or wallhaven-{id}-{variant}.{ext} for thumbnails
+    ///
+    ///    Ex. "{id}_{resolution}_{category}_{purity}.{ext}"
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+           value_parser = clap::value_parser!(Template),
+    )]
+    pub name_template: Option<Template>,
+
+    /// Layout
+    ///
+    ///    Save wallpapers in subdirectories of --path,
+    ///    same placeholders as --name-template, / separated
+    ///    {year} {month} {day} - parts of created_at
+    ///
+    ///    Ex. "{purity}/{category}/{ratio}", "{year}/{month}"
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+           value_parser = clap::value_parser!(Template),
+    )]
+    pub layout: Option<Template>,
+
+    /// Variant
+    ///
+    ///    Image to download
+    ///    full - the wallpaper itself
+    ///    thumb-small, thumb-large, thumb-original - its thumbnails,
+    ///    thumb-original keeps the wallpaper aspect ratio
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+           ignore_case = true,
+           default_value = "full",
+           value_parser = ["full", "thumb-small", "thumb-large", "thumb-original"],
+    )]
+    variant: String,
+
+    /// Sidecar
+    ///
+    ///    Write the full wallpaper info (tags, uploader, source...)
+    ///    to <image>.json next to each downloaded wallpaper,
+    ///    one api request each under --rate-limit,
+    ///    the api can't fetch several at once
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = "DOWNLOAD",
+    )]
+    pub sidecar: bool,
+}
+
+impl DownloadArgs {
+    /// Paths of the downloaded wallpapers
+    pub fn namer(&self) -> FileNamer {
+        let variant = self.variant();
+        let name = self.name_template.clone().unwrap_or_else(|| Template::default_name(variant));
+        let namer = FileNamer::new(name)
+            .with_variant(variant);
+        match &self.layout {
+            Some(layout) => namer.with_layout(layout.clone()),
+            None => namer,
+        }
+    }
+
+    pub fn on_exists(&self) -> ExistsPolicy {
+        // Already validated by clap
+        self.on_exists.parse().unwrap_or_default()
+    }
+
+    pub fn variant(&self) -> Variant {
+        // Already validated by clap
+        self.variant.parse().unwrap_or_default()
+    }
+}
+
+
+#[derive(Debug, Args, Serialize)]
+pub struct PagingArgs {
     /// Page
     ///
     ///    Select page of results
@@ -197,46 +455,119 @@ pub struct SearchArgs {
            //default_value_t = 1,
            value_parser = clap::value_parser!(u32).range(1..),
     )]
-    page: Option<u32>,
+    pub page: Option<u32>,
 
-    /// Seed
+    /// Pages
     ///
-    ///     Optional seed for random results
-    ///     [a-zA-Z0-9]{6}
+    ///    Number of pages to fetch, starting at --page
     #[clap(long,
            verbatim_doc_comment,
            help_heading = Some("SEARCH PREFERENCES"),
-           //default_value_t = 1,
-           value_parser = clap::value_parser!(Seed),
+           value_parser = clap::value_parser!(u32).range(1..),
     )]
-    seed: Option<Seed>,
+    pages: Option<u32>,
+
+    /// All
+    ///
+    ///    Fetch every page until the last one
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           conflicts_with = "pages",
+    )]
+    all: bool,
+
+    /// Limit
+    ///
+    ///    Stop after this many wallpapers,
+    ///    fetching as many pages as needed unless --pages is set
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    limit: Option<u64>,
+
+    /// Filter
+    ///
+    ///    Only keep results matching the expression,
+    ///    checked before --limit is counted
+    ///    field op value, joined with && || ! ( )
+    ///    ops: == != < <= > >= ~ (contains)
+    ///    fields: views, favorites, file_size, created_at,
+    ///            width, height, resolution, ratio, purity,
+    ///            category, file_type, colors, id, source
+    ///
+    ///    Ex. "favorites>100 && file_size<5MB && created_at>2024-01-01"
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(Filter),
+    )]
+    pub filter: Option<Filter>,
 }
 
+impl PagingArgs {
+    /// Number of pages to fetch, `None` for all of them
+    pub fn page_count(&self) -> Option<u32> {
+        if self.all {
+            None
+        } else if self.pages.is_some() {
+            self.pages
+        } else if self.limit.is_some() {
+            // Only the limit bounds the search
+            None
+        } else {
+            Some(1)
+        }
+    }
 
-fn valid_color(s: &str) -> Result<String, String> {
-    let s = 
-    if s.starts_with("#") {
-        &s[1..]
-    } else {
-        s
-    };
+    /// Maximum number of wallpapers to fetch
+    pub fn limit(&self) -> Option<usize> {
+        self.limit.map(|l| l as usize)
+    }
+}
 
-    let valid_hex = s.chars().into_iter().all(|c| c.is_digit(16));
 
-    if valid_hex && s.len() == 6 {
-        return Ok(String::from(s));
-    } else {
-        return Err(String::from(format!("{s} is not a valid hex color")));
+impl SearchArgs {
+    /// Colors out of the palette and what they are snapped to
+    pub fn color_substitutions(&self) -> Vec<(Color, Color)> {
+        self.colors.iter()
+            .filter(|c| !c.in_palette())
+            .map(|c| (*c, c.snap()))
+            .collect()
     }
 }
 
-fn valid_wallpaper_id(s: &str) -> Result<String, String> {
-    let valid_format = s.chars().into_iter().all(|c| c.is_ascii_digit() || c.is_ascii_alphabetic());
+
+impl From<&SearchArgs> for SearchParams {
+    fn from(args: &SearchArgs) -> Self {
+        Self {
+            query: args.query.clone(),
+            categories: args.categories,
+            purity: args.purity,
+            sorting: args.sorting.clone(),
+            order: args.order.clone(),
+            toprange: args.toprange.clone(),
+            atleast: args.atleast,
+            resolutions: args.resolutions.clone(),
+            ratios: args.ratios.clone(),
+            colors: args.colors.iter().map(Color::snap).collect(),
+            page: args.paging.page,
+            seed: args.seed.clone(),
+        }
+    }
+}
+
+
+
+pub fn valid_wallpaper_id(s: &str) -> Result<String, String> {
+    let valid_format = s.chars().all(|c| c.is_ascii_digit() || c.is_ascii_alphabetic());
 
     if valid_format && s.len() == 6 {
-        return Ok(String::from(s));
+        Ok(String::from(s))
     } else {
-        return Err(String::from(format!("{s} is not a valid wallpaper id")));
+        Err(format!("{s} is not a valid wallpaper id"))
     }
 }
 
@@ -269,200 +600,263 @@ pub struct UserCollectionsArgs {
     ///
     /// Get username public collections
     /// If no username provided, gets all api key account collections
-    username: Option<String>
+    pub username: Option<String>
 }
 
+#[derive(Debug, Args)]
+pub struct CollectionArgs {
+    /// Username of the collection owner
+    pub username: String,
 
-#[derive(Debug, Default, Clone)]
-pub struct SearchQuery {
-    tags: Option<Vec<String>>,
-    username: Option<String>,
-    id: Option<String>, //Cant be combined
-    filetype: Option<String>,  //type:{png/jpg}
-    like: Option<String>,
-}
+    /// ID of collection
+    pub id: i32,
 
-impl FromStr for SearchQuery {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut q = Self::default();
-        let mut tags = Vec::<String>::default();
-
-        for token in s.split(" ") {
-            // Get parameters key:value
-            let t: Vec<&str> = token.split(":").collect();
-            if t.len() == 2 {
-                let key = t[0];
-                let value = t[1];
-
-                match key {
-                    "id" => {
-                        // Exclusive parameter
-                        // Id is a tag number
-                        // Maybe i should force a casting, even if api is resilient to non integer id?
-                        let mut q = Self::default();
-                        q.id = Some(String::from(value));
-                        return Ok(q);
-                    },
-                    "type" => {
-                        if value == "png" || value == "jpg" {
-                            q.filetype = Some(String::from(value));
-                        } else {
-                            return Err(String::from("Invalid file type - only accept png or jpg"));
-                        }
-                    },
-                    "like" => {
-                        // Wallpaper ID
-                        // As of now ID is length 6 alphanumerical String
-                        // Maybe should enforce it by explicit check, and return err
-                        q.like = Some(String::from(value));
-                    },
-                    _ => {
-                        return Err(String::from(format!("{key}:{value} is not a valid query")));
-                    }
-                }
-
-                continue;
-            } 
-
-            // Get username if any
-            if token.starts_with("@") {
-                q.username = Some(String::from(&token[1..]));
-                continue;
-            }
-
-            // Get tags - merging fuzzily with + and - tags
-            tags.push(token.to_string());
+    #[clap(flatten)]
+    pub download: DownloadArgs,
 
-        }
+    /// Purity
+    ///
+    ///    Turn purities on(1) or off(0)
+    ///    *NSFW requires a valid API key*
+    ///    (sfw/sketchy/nsfw).
+    ///    Or list them by name
+    ///
+    ///    Ex. 011, sfw,sketchy
+    #[clap(short = 'p',
+           long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(Purity),
+    )]
+    purity: Option<Purity>,
 
-        if !tags.is_empty() {
-            q.tags = Some(tags)
-        }
+    #[clap(flatten)]
+    pub paging: PagingArgs,
+}
 
-        return Ok(q);
+impl From<&CollectionArgs> for CollectionParams {
+    fn from(args: &CollectionArgs) -> Self {
+        Self {
+            username: args.username.clone(),
+            id: args.id,
+            purity: args.purity,
+            page: args.paging.page,
+        }
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct Seed(String);
-
-impl FromStr for Seed {
-    type Err = String;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() == 6 && s.chars().all(|c| c.is_ascii_alphabetic() || c.is_ascii_digit()) {
-            return Ok(Seed(String::from(s)));
-        } else {
-            return Err(format!("{s} is an invalid seed"));
-        }
-    }
+#[derive(Debug, Args)]
+pub struct SavedArgs {
+    #[clap(subcommand)]
+    pub command: SavedCommands,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum SavedCommands {
+    /// Save search flags under a name, replacing any search with that name
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    Add {
+        /// Name of the saved search
+        name: String,
+
+        /// Search flags, as given to the search command
+        ///
+        ///    Ex. wallhaven saved add cats --query "+cat" --purity sfw -S ~/wallpapers
+        #[clap(verbatim_doc_comment,
+               required = true,
+               allow_hyphen_values = true,
+               multiple_values = true,
+        )]
+        flags: Vec<String>,
+    },
+    /// List saved search names
+    List,
+    /// Show the flags of a saved search
+    Show {
+        /// Name of the saved search
+        name: String,
+    },
+    /// Remove a saved search
+    Rm {
+        /// Name of the saved search
+        name: String,
+    },
+    /// Run a saved search, flags given here override the saved ones
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    Run {
+        /// Name of the saved search
+        name: String,
+
+        /// Search flags overriding the saved ones
+        #[clap(allow_hyphen_values = true,
+               multiple_values = true,
+        )]
+        overrides: Vec<String>,
+    },
+}
 
-impl Url for SearchArgs {
-    fn to_url(&self, base_url: &str) -> String {
-        let mut params = Vec::<String>::new();
 
-        // Search 
-        if let Some(colors) = &self.colors {
-            params.push(format!("colors={colors}"))
-        } else {
-            if let Some(q) = &self.query {
-                if let Some(id) = &q.id {
-                    params.push(format!("q=id:{id}"));
-                } else {
-                    let mut query = Vec::<String>::new();
-
-                    if let Some(tags) = &q.tags {
-                        query.push(tags.join(" "));
-                    }
-                    if let Some(username) = &q.username{
-                        query.push(format!("@{username}"));
-                    }
-                    if let Some(ft) = &q.filetype {
-                        query.push(format!("type:{ft}"));
-                    }
-                    if let Some(w) = &q.like {
-                        query.push(format!("like:{w}"));
-                    }
-
-                    params.push(format!("q={}", query.join(" ")));
-                }
-            }
-        }
+#[derive(Debug, Args)]
+pub struct ExploreArgs {
+    /// ID of the wallpaper to start from
+    #[clap(value_parser = valid_wallpaper_id)]
+    pub id: String,
 
-        // Search preferences
-        if let Some(categories) = &self.categories {
-            params.push(format!("categories={}", categories));
-        }
-        if let Some(purity) = &self.purity{
-            params.push(format!("purity={}", purity));
-        }
-        if let Some(page) = self.page {
-            params.push(format!("page={}", page));
-        }
-        if let Some(seed) = &self.seed {
-            params.push(format!("seed={}", seed.0));
-        }
+    #[clap(flatten)]
+    pub download: DownloadArgs,
 
-        // Sorting
-        if let Some(order) = &self.order {
-            params.push(format!("order={}", order.to_ascii_lowercase()));
-        }
-        if let Some(sorting) = &self.sorting{
-            params.push(format!("sorting={}", sorting.to_ascii_lowercase()));
-        }
-        if let Some(toprange) = &self.toprange{
-            params.push(format!("topRange={}", toprange.to_ascii_lowercase()));
-        }
+    /// Depth
+    ///
+    ///    Levels of similar wallpapers to follow,
+    ///    1 only searches the ones similar to <ID>
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("EXPLORE"),
+           default_value_t = 2,
+           value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    pub depth: u32,
 
-        // Wallpaper Preferences
-        if let Some(atleast) = &self.atleast {
-            params.push(format!("atleast={}", atleast.to_ascii_lowercase()));
-        }
-        if let Some(resolutions) = &self.resolutions {
-            params.push(format!("resolutions={}", resolutions.to_ascii_lowercase()));
-        }
-        if let Some(ratios) = &self.ratios {
-            params.push(format!("ratios={}", ratios.to_ascii_lowercase()));
-        }
+    /// Fan out
+    ///
+    ///    New wallpapers kept from each similar search,
+    ///    out of its first page of results (1..24)
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("EXPLORE"),
+           default_value_t = 5,
+           value_parser = clap::value_parser!(u16).range(1..=24),
+    )]
+    pub fan_out: u16,
 
+    /// Checkpoint
+    ///
+    ///    File to save progress to after each search,
+    ///    an interrupted exploration resumes from it
+    ///    and it is removed once done
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("EXPLORE"),
+    )]
+    pub checkpoint: Option<PathBuf>,
 
-        return format!("{base_url}/search?{}", params.join("&"));
-    }
-}
+    /// Categories
+    ///
+    ///    Turn categories on(1) or off(0)
+    ///    (general/anime/people).
+    ///    Or list them by name
+    ///
+    ///    Ex. 010, anime,people
+    #[clap(short = 'c',
+           long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(Category),
+    )]
+    categories: Option<Category>,
 
+    /// Purity
+    ///
+    ///    Turn purities on(1) or off(0)
+    ///    *NSFW requires a valid API key*
+    ///    (sfw/sketchy/nsfw).
+    ///    Or list them by name
+    ///
+    ///    Ex. 011, sfw,sketchy
+    #[clap(short = 'p',
+           long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(Purity),
+    )]
+    purity: Option<Purity>,
 
-impl Url for WallpaperInfoArgs {
-    fn to_url(&self, base_url: &str) -> String {
-        return format!("{base_url}/w/{}", self.id);
-    }
-}
+    /// Atleast
+    ///
+    ///    Set The minimum resolution allowed
+    ///    Ex. 1920x1080.
+    #[clap(short = 'a',
+           long,
+           verbatim_doc_comment,
+           help_heading = Some("WALLPAPER PREFERENCES"),
+           value_parser = clap::value_parser!(Resolution),
+    )]
+    atleast: Option<Resolution>,
 
-impl Url for TagInfoArgs {
-    fn to_url(&self, base_url: &str) -> String {
-        return format!("{base_url}/tag/{}", self.id);
+    /// Ratios
+    ///
+    ///    List of aspect ratios (WxH or W:H)
+    ///    or landscape, portrait, ultrawide
+    ///
+    ///    Ex. 16x9,16:10
+    #[clap(short = 'R',
+           long,
+           verbatim_doc_comment,
+           help_heading = Some("WALLPAPER PREFERENCES"),
+           value_delimiter = ',',
+           value_parser = clap::value_parser!(RatioFilter),
+    )]
+    ratios: Vec<RatioFilter>,
 
-    }
+    /// Filter
+    ///
+    ///    Only keep and follow wallpapers matching the expression,
+    ///    see search --help
+    ///
+    ///    Ex. "favorites>100 && file_size<5MB"
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH PREFERENCES"),
+           value_parser = clap::value_parser!(Filter),
+    )]
+    pub filter: Option<Filter>,
 }
 
-impl Url for UserSettingsArgs {
-    fn to_url(&self, base_url: &str) -> String {
-        return format!("{base_url}/settings");
+impl From<&ExploreArgs> for SearchParams {
+    fn from(args: &ExploreArgs) -> Self {
+        Self {
+            categories: args.categories,
+            purity: args.purity,
+            atleast: args.atleast,
+            ratios: args.ratios.clone(),
+            ..Self::default()
+        }
     }
 }
 
-impl Url for UserCollectionsArgs {
-    fn to_url(&self, base_url: &str) -> String {
-        match &self.username {
-            Some(username) => {
-                return format!("{base_url}/collections/{username}");
-            },
-            None => {
-                return format!("{base_url}/collections");
-            }
-        }
-    }
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Directory of downloaded wallpapers, subdirectories included
+    pub dir: PathBuf,
+
+    /// Remote
+    ///
+    ///    Check size and type against the wallpaper info,
+    ///    for files named after a wallpaper id (wallhaven-<ID>.<ext>)
+    ///    One api request each
+    #[clap(long,
+           verbatim_doc_comment,
+    )]
+    pub remote: bool,
+
+    /// Refetch
+    ///
+    ///    Download again the bad files named after a wallpaper id
+    #[clap(long,
+           verbatim_doc_comment,
+    )]
+    pub refetch: bool,
+
+    /// Jobs
+    ///
+    ///    Number of wallpapers to download at the same time
+    #[clap(short = 'j',
+           long,
+           verbatim_doc_comment,
+           default_value_t = 1,
+           value_parser = clap::value_parser!(u16).range(1..),
+    )]
+    pub jobs: u16,
 }
@@ -0,0 +1,445 @@
+//! Persistent configuration file
+//!
+//! `~/.config/wallhaven/config.toml` holds default values for any command flag,
+//! keys are the long flag names (`-` or `_` separated), plus the API key.
+//! `[profile.<name>]` tables override them when selected with `--profile`.
+//!
+//! ```toml
+//! api_key = "your_api_key"
+//! path = "~/wallpapers"
+//! categories = "110"
+//! atleast = "1920x1080"
+//! jobs = 4
+//!
+//! [profile.toplist]
+//! sorting = "TOPLIST"
+//! toprange = "1W"
+//! path = "~/wallpapers/toplist"
+//! ```
+//!
+//! Config values are turned into flags ahead of the command line ones,
+//! so they go through the same validation and flags given on the command line win.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::CommandFactory;
+use serde::Deserialize;
+
+use crate::args::CLIArgs;
+
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(flatten)]
+    defaults: toml::Table,
+    #[serde(default)]
+    profile: HashMap<String, toml::Table>,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub api_key: Option<String>,
+    values: toml::Table,
+}
+
+/// Default config file path, `$XDG_CONFIG_HOME/wallhaven/config.toml`
+/// or `~/.config/wallhaven/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// `$XDG_CONFIG_HOME/wallhaven` or `~/.config/wallhaven`
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+
+    Some(config_dir.join("wallhaven"))
+}
+
+/// `$XDG_STATE_HOME/wallhaven` or `~/.local/state/wallhaven`
+pub fn state_dir() -> Option<PathBuf> {
+    let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".local").join("state"),
+    };
+
+    Some(state_dir.join("wallhaven"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Write `content` to `<path>.part` and rename it to `path`,
+/// an interrupted write leaves the previous file as it was
+pub fn write_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let part = wallhaven::part_path(path);
+    std::fs::write(&part, content)?;
+    std::fs::rename(&part, path)
+}
+
+impl Config {
+    /// Load the config file at `path`, or the default one if it exists,
+    /// with `profile` values over the defaults
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Self, String> {
+        let file = match path {
+            Some(p) => Some(read(p)?),
+            None => match default_path() {
+                Some(p) if p.exists() => Some(read(&p)?),
+                _ => None,
+            },
+        };
+        let file = file.unwrap_or_default();
+
+        check_keys(&file.defaults)?;
+        for values in file.profile.values() {
+            check_keys(values)?;
+        }
+
+        let mut values = file.defaults;
+        if let Some(name) = profile {
+            let profile_values = file.profile.get(name)
+                .ok_or(format!("Profile '{}' not found in config", name))?;
+
+            values.extend(profile_values.clone());
+        }
+
+        let api_key = match values.remove("api_key") {
+            Some(toml::Value::String(k)) => Some(k),
+            Some(_) => return Err(String::from("Config api_key must be a string")),
+            None => None,
+        };
+
+        Ok(Self { api_key, values })
+    }
+
+    /// Flag defaults from `values`, keyed like the config file
+    pub fn from_values(values: toml::Table) -> Result<Self, String> {
+        check_keys(&values)?;
+
+        Ok(Self { api_key: None, values })
+    }
+
+    /// Insert config values as flags of the subcommand in `argv`,
+    /// skipping the ones already given or conflicting with a given one
+    pub fn apply(&self, mut argv: Vec<OsString>) -> Vec<OsString> {
+        let cmd = CLIArgs::command();
+
+        let Some(sub_index) = subcommand_index(&cmd, &argv) else {
+            return argv;
+        };
+        let Some(sub) = argv[sub_index].to_str().and_then(|name| cmd.find_subcommand(name)) else {
+            return argv;
+        };
+
+        let given = given_ids(&cmd, sub, &argv);
+
+        let mut flags = Vec::<OsString>::new();
+        for (key, value) in &self.values {
+            let long = flag_name(key);
+            // Only the flags this subcommand takes
+            let Some(arg) = find_arg(sub, &long).or_else(|| find_arg(&cmd, &long)) else {
+                continue;
+            };
+
+            // Flags on the command line win, even over conflicting ones
+            if given.contains(arg.get_id()) || conflicts_with_given(sub, arg, &given) {
+                continue;
+            }
+
+            match value {
+                toml::Value::Boolean(true) => flags.push(OsString::from(format!("--{long}"))),
+                toml::Value::Boolean(false) => {},
+                v => {
+                    let value = value_string(v);
+                    let value = if long == "path" { expand_home(&value) } else { value };
+                    flags.push(OsString::from(format!("--{long}={value}")));
+                },
+            }
+        }
+
+        argv.splice(sub_index + 1..sub_index + 1, flags);
+        argv
+    }
+}
+
+/// Every key must be the api key or the flag of some command
+fn check_keys(values: &toml::Table) -> Result<(), String> {
+    let cmd = CLIArgs::command();
+
+    for key in values.keys().filter(|k| *k != "api_key") {
+        let long = flag_name(key);
+        let known = find_arg(&cmd, &long).is_some()
+            || cmd.get_subcommands().any(|sub| find_arg(sub, &long).is_some());
+
+        if !known {
+            return Err(format!("Unknown config key '{}'", key));
+        }
+    }
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<ConfigFile, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config {} - {}", path.display(), e))?;
+
+    toml::from_str(&content)
+        .map_err(|e| format!("Invalid config {} - {}", path.display(), e))
+}
+
+pub fn flag_name(key: &str) -> String {
+    key.replace('_', "-")
+}
+
+pub fn find_arg<'a, 'help>(cmd: &'a clap::Command<'help>, long: &str) -> Option<&'a clap::Arg<'help>> {
+    cmd.get_arguments().find(|a| a.get_long() == Some(long))
+}
+
+/// Whether `arg` conflicts with any of the `given` args of `cmd`,
+/// declared on either side
+fn conflicts_with_given(cmd: &clap::Command, arg: &clap::Arg, given: &HashSet<&str>) -> bool {
+    // Global args aren't part of the subcommand until it is built
+    if find_arg(cmd, arg.get_long().unwrap_or_default()).is_none() {
+        return false;
+    }
+
+    cmd.get_arg_conflicts_with(arg).iter().any(|a| given.contains(a.get_id()))
+        || cmd.get_arguments()
+            .filter(|a| given.contains(a.get_id()))
+            .any(|a| cmd.get_arg_conflicts_with(a).iter().any(|c| c.get_id() == arg.get_id()))
+}
+
+fn value_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        // Lists are comma separated flag values
+        toml::Value::Array(a) => a.iter().map(value_string).collect::<Vec<_>>().join(","),
+        v => v.to_string(),
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => String::from(path),
+    }
+}
+
+/// Position of the subcommand name, skipping the global flags before it
+pub fn subcommand_index(cmd: &clap::Command, argv: &[OsString]) -> Option<usize> {
+    let mut i = 1;
+
+    while let Some(token) = argv.get(i).and_then(|t| t.to_str()) {
+        let Some(long) = token.strip_prefix("--") else {
+            return Some(i);
+        };
+
+        let takes_value = !long.contains('=')
+            && find_arg(cmd, long).map(|a| a.is_takes_value_set()).unwrap_or(false);
+        i += if takes_value { 2 } else { 1 };
+    }
+
+    None
+}
+
+/// Ids of the flags given in `argv`, either of `sub` or global ones
+pub fn given_ids<'a, 'help>(cmd: &'a clap::Command<'help>, sub: &'a clap::Command<'help>, argv: &[OsString]) -> HashSet<&'a str> {
+    let find_long = |long: &str| find_arg(sub, long).or_else(|| find_arg(cmd, long));
+    let find_short = |short: char| {
+        sub.get_arguments().chain(cmd.get_arguments())
+            .find(|a| a.get_short() == Some(short))
+    };
+
+    let mut ids = HashSet::new();
+    let mut tokens = argv.iter().skip(1).filter_map(|t| t.to_str());
+
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            break;
+        }
+
+        if let Some(flag) = token.strip_prefix("--") {
+            let (long, value) = match flag.split_once('=') {
+                Some((long, _)) => (long, true),
+                None => (flag, false),
+            };
+            if let Some(arg) = find_long(long) {
+                ids.insert(arg.get_id());
+                if !value && arg.is_takes_value_set() {
+                    tokens.next();
+                }
+            }
+        } else if let Some(shorts) = token.strip_prefix('-') {
+            // Grouped shorts, the first one taking a value ends the group
+            for (i, short) in shorts.char_indices() {
+                let Some(arg) = find_short(short) else {
+                    break;
+                };
+                ids.insert(arg.get_id());
+                if arg.is_takes_value_set() {
+                    if i + short.len_utf8() == shorts.len() {
+                        tokens.next();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    ids
+}
+
+/// Value of a global `--flag value` or `--flag=value` anywhere in `argv`,
+/// needed before the command line is parsed
+pub fn global_value(argv: &[OsString], long: &str) -> Option<OsString> {
+    let flag = format!("--{long}");
+    let prefix = format!("--{long}=");
+
+    let mut tokens = argv.iter().skip(1);
+    while let Some(token) = tokens.next() {
+        let Some(t) = token.to_str() else {
+            continue;
+        };
+        if t == "--" {
+            break;
+        }
+        if t == flag {
+            return tokens.next().cloned();
+        }
+        if let Some(value) = t.strip_prefix(&prefix) {
+            return Some(OsString::from(value));
+        }
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn argv(tokens: &[&str]) -> Vec<OsString> {
+        tokens.iter().map(OsString::from).collect()
+    }
+
+    fn config(toml: &str) -> Config {
+        Config::from_values(toml::from_str(toml).unwrap()).unwrap()
+    }
+
+    fn search_ids(tokens: &[&str]) -> HashSet<String> {
+        let cmd = CLIArgs::command();
+        let sub = cmd.find_subcommand("search").unwrap();
+
+        given_ids(&cmd, sub, &argv(tokens)).into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn finds_subcommand_after_globals() {
+        let cmd = CLIArgs::command();
+
+        let tokens = argv(&["wallhaven", "--base-url", "http://x", "--rate-limit=5", "--profile", "p", "search", "-q", "cat"]);
+        assert_eq!(subcommand_index(&cmd, &tokens), Some(6));
+        assert_eq!(subcommand_index(&cmd, &argv(&["wallhaven", "search"])), Some(1));
+        assert_eq!(subcommand_index(&cmd, &argv(&["wallhaven", "--config", "c.toml"])), None);
+    }
+
+    #[test]
+    fn collects_given_ids() {
+        let ids = search_ids(&["wallhaven", "--retries", "2", "search", "-q", "cat", "--path=/w", "-j4", "--all", "--on-exists", "rename"]);
+        let expected = ["retries", "query", "path", "jobs", "all", "on-exists"];
+        assert_eq!(ids, expected.iter().map(|s| s.to_string()).collect());
+
+        // Values aren't taken for flags, nor anything after --
+        let ids = search_ids(&["wallhaven", "search", "-q", "--all", "-S", "/w", "--", "--pages", "2"]);
+        assert_eq!(ids, ["query", "path"].iter().map(|s| s.to_string()).collect());
+    }
+
+    #[test]
+    fn finds_global_values() {
+        assert_eq!(global_value(&argv(&["wallhaven", "--profile=a", "search"]), "profile"), Some(OsString::from("a")));
+        assert_eq!(global_value(&argv(&["wallhaven", "search", "--config", "c.toml"]), "config"), Some(OsString::from("c.toml")));
+        assert_eq!(global_value(&argv(&["wallhaven", "search", "--", "--config", "c.toml"]), "config"), None);
+    }
+
+    #[test]
+    fn applies_missing_flags() {
+        let config = config(r#"
+            jobs = 4
+            path = "/w"
+            query = "dog"
+            ratios = ["16x9", "ultrawide"]
+            sidecar = true
+            all = false
+            rate_limit = 10
+        "#);
+
+        let applied = config.apply(argv(&["wallhaven", "--base-url", "http://x", "search", "-q", "cat"]));
+        assert_eq!(applied, argv(&[
+            "wallhaven", "--base-url", "http://x", "search",
+            "--jobs=4", "--path=/w", "--rate-limit=10", "--ratios=16x9,ultrawide", "--sidecar",
+            "-q", "cat",
+        ]));
+        assert!(CLIArgs::try_parse_from(applied).is_ok());
+    }
+
+    #[test]
+    fn skips_conflicting_flags() {
+        // --all conflicts with --pages
+        let applied = config("all = true").apply(argv(&["wallhaven", "search", "-q", "cat", "--pages", "2"]));
+        assert_eq!(applied, argv(&["wallhaven", "search", "-q", "cat", "--pages", "2"]));
+
+        let applied = config("pages = 2").apply(argv(&["wallhaven", "search", "-q", "cat", "--all"]));
+        assert_eq!(applied, argv(&["wallhaven", "search", "-q", "cat", "--all"]));
+        assert!(CLIArgs::try_parse_from(applied).is_ok());
+    }
+
+    #[test]
+    fn replaces_files_whole() {
+        let path = std::env::temp_dir().join(format!("wallhaven-write-{}.toml", std::process::id()));
+        std::fs::write(&path, "jobs = 2").unwrap();
+
+        write_file(&path, "jobs = 4").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "jobs = 4");
+        assert!(!wallhaven::part_path(&path).exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn merges_profiles() {
+        let path = std::env::temp_dir().join(format!("wallhaven-config-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"
+            api_key = "key"
+            jobs = 2
+            path = "/w"
+
+            [profile.toplist]
+            sorting = "TOPLIST"
+            path = "/w/toplist"
+        "#).unwrap();
+
+        let config = Config::load(Some(&path), Some("toplist")).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("key"));
+        let applied = config.apply(argv(&["wallhaven", "search", "-q", "cat"]));
+        assert_eq!(applied, argv(&["wallhaven", "search", "--jobs=2", "--path=/w/toplist", "--sorting=TOPLIST", "-q", "cat"]));
+
+        assert!(Config::load(Some(&path), Some("nope")).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(Config::from_values(toml::from_str("colour = \"red\"").unwrap()).is_err());
+        assert!(Config::from_values(toml::from_str("fan_out = 3").unwrap()).is_ok());
+    }
+}
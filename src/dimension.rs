@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+
+/// Wallpaper size in pixels, `WxH` (ex. `1920x1080`)
+///
+/// Ordered by pixel count, then width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn pixels(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Whether it is at least `other` in both dimensions
+    pub fn fits(&self, other: &Resolution) -> bool {
+        self.width >= other.width && self.height >= other.height
+    }
+
+    pub fn ratio(&self) -> AspectRatio {
+        AspectRatio::new(self.width, self.height)
+    }
+}
+
+impl Ord for Resolution {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.pixels().cmp(&other.pixels())
+            .then(self.width.cmp(&other.width))
+    }
+}
+
+impl PartialOrd for Resolution {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (width, height) = split_pair(s, &['x', 'X'])
+            .ok_or(format!("{s} is not a valid resolution - expected WxH (ex. 1920x1080)"))?;
+
+        Ok(Self::new(width, height))
+    }
+}
+
+
+/// Width to height proportion, `WxH` or `W:H` (ex. `16x9`), kept reduced
+///
+/// The api reports wallpaper ratios as decimals (ex. `1.78` or `1`),
+/// those are read as exact fractions (`89x50`, `1x1`).
+/// Ordered by value, narrowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AspectRatio {
+    pub fn new(width: u32, height: u32) -> Self {
+        let d = gcd(width as u64, height as u64).max(1) as u32;
+
+        Self {
+            width: width / d,
+            height: height / d,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Same value, up to the api rounding to 2 decimals
+    pub fn approx_eq(&self, other: &AspectRatio) -> bool {
+        (self.value() - other.value()).abs() < 0.01
+    }
+}
+
+impl Ord for AspectRatio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = self.width as u64 * other.height as u64;
+        let rhs = other.width as u64 * self.height as u64;
+        lhs.cmp(&rhs)
+    }
+}
+
+impl PartialOrd for AspectRatio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some((width, height)) = split_pair(s, &['x', 'X', ':']) {
+            return Ok(Self::new(width, height));
+        }
+
+        // Decimal form, an integer with an optional fraction of any length
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        let digits = |d: &str| d.chars().all(|c| c.is_ascii_digit());
+        let decimal = Some((int, frac))
+            .filter(|(int, frac)| !int.is_empty() && digits(int) && digits(frac) && frac.len() <= 9)
+            .and_then(|(int, frac)| {
+                let denominator = 10u64.pow(frac.len() as u32);
+                let numerator = int.parse::<u64>().ok()?
+                    .checked_mul(denominator)?
+                    .checked_add(if frac.is_empty() { 0 } else { frac.parse().ok()? })?;
+                let d = gcd(numerator, denominator);
+
+                Some((u32::try_from(numerator / d).ok()?, u32::try_from(denominator / d).ok()?))
+            })
+            .filter(|(width, _)| *width > 0);
+
+        match decimal {
+            Some((width, height)) => Ok(Self::new(width, height)),
+            None => Err(format!("{s} is not a valid aspect ratio - expected WxH or W:H (ex. 16x9)")),
+        }
+    }
+}
+
+
+/// Ratios to search for, an exact ratio or a keyword
+///
+///    landscape - wider than tall
+///    portrait - taller than wide
+///    ultrawide - 21x9, 32x9 and 48x9
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RatioFilter {
+    Exact(AspectRatio),
+    Landscape,
+    Portrait,
+    Ultrawide,
+}
+
+/// Ultrawide ratios the api knows about
+pub const ULTRAWIDE: [AspectRatio; 3] = [
+    AspectRatio { width: 21, height: 9 },
+    AspectRatio { width: 32, height: 9 },
+    AspectRatio { width: 48, height: 9 },
+];
+
+impl RatioFilter {
+    pub fn matches(&self, ratio: &AspectRatio) -> bool {
+        match self {
+            Self::Exact(r) => r.approx_eq(ratio),
+            Self::Landscape => ratio.width > ratio.height,
+            Self::Portrait => ratio.width < ratio.height,
+            Self::Ultrawide => ULTRAWIDE.iter().any(|r| r.approx_eq(ratio)),
+        }
+    }
+
+    /// Values of the `ratios` api parameter, ultrawide isn't a keyword there
+    pub fn api_values(&self) -> Vec<String> {
+        match self {
+            Self::Ultrawide => ULTRAWIDE.iter().map(AspectRatio::to_string).collect(),
+            r => vec![r.to_string()],
+        }
+    }
+}
+
+impl fmt::Display for RatioFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(r) => write!(f, "{r}"),
+            Self::Landscape => write!(f, "landscape"),
+            Self::Portrait => write!(f, "portrait"),
+            Self::Ultrawide => write!(f, "ultrawide"),
+        }
+    }
+}
+
+impl FromStr for RatioFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "landscape" => Ok(Self::Landscape),
+            "portrait" => Ok(Self::Portrait),
+            "ultrawide" => Ok(Self::Ultrawide),
+            _ => s.parse().map(Self::Exact),
+        }
+    }
+}
+
+
+/// `<W><sep><H>` with both sides positive integers
+fn split_pair(s: &str, separators: &[char]) -> Option<(u32, u32)> {
+    let (w, h) = s.split_once(separators)?;
+    let w = w.trim().parse::<u32>().ok().filter(|w| *w > 0)?;
+    let h = h.trim().parse::<u32>().ok().filter(|h| *h > 0)?;
+
+    Some((w, h))
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+
+macro_rules! impl_serde_str {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s = String::deserialize(deserializer)?;
+                    s.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+impl_serde_str!(Resolution, AspectRatio, RatioFilter);
+
+
+/// Serialize a ratio as a 2 decimals string, as the api does for wallpapers
+pub mod as_decimal {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(ratio: &AspectRatio, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:.2}", ratio.value()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AspectRatio, D::Error> {
+        AspectRatio::deserialize(deserializer)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_resolutions() {
+        assert_eq!("1920x1080".parse::<Resolution>().unwrap(), Resolution::new(1920, 1080));
+        assert_eq!(" 2560X1440 ".parse::<Resolution>().unwrap(), Resolution::new(2560, 1440));
+        assert!("1920".parse::<Resolution>().is_err());
+        assert!("0x1080".parse::<Resolution>().is_err());
+        assert!("1920x-1".parse::<Resolution>().is_err());
+    }
+
+    #[test]
+    fn orders_resolutions_by_pixels() {
+        let mut r: Vec<Resolution> = ["3840x2160", "1920x1080", "2560x1080"].iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        r.sort();
+
+        assert_eq!(r.iter().map(Resolution::to_string).collect::<Vec<_>>(), ["1920x1080", "2560x1080", "3840x2160"]);
+        assert!(Resolution::new(2560, 1440).fits(&Resolution::new(1920, 1080)));
+    }
+
+    #[test]
+    fn parses_ratios() {
+        assert_eq!("16:9".parse::<AspectRatio>().unwrap(), AspectRatio::new(16, 9));
+        assert_eq!("32x18".parse::<AspectRatio>().unwrap().to_string(), "16x9");
+        assert_eq!("1.78".parse::<AspectRatio>().unwrap(), AspectRatio::new(89, 50));
+        assert_eq!("1.5".parse::<AspectRatio>().unwrap(), AspectRatio::new(3, 2));
+        assert!("1.78".parse::<AspectRatio>().unwrap().approx_eq(&AspectRatio::new(16, 9)));
+        assert!(AspectRatio::new(4, 3) < AspectRatio::new(16, 9));
+        assert_eq!("2".parse::<AspectRatio>().unwrap(), AspectRatio::new(2, 1));
+        assert_eq!("0.5625".parse::<AspectRatio>().unwrap(), AspectRatio::new(9, 16));
+        assert!("16/9".parse::<AspectRatio>().is_err());
+        assert!("0".parse::<AspectRatio>().is_err());
+        assert!(".5".parse::<AspectRatio>().is_err());
+        assert!("1.-5".parse::<AspectRatio>().is_err());
+    }
+
+    #[test]
+    fn deserializes_api_ratios() {
+        #[derive(Deserialize)]
+        struct W {
+            #[serde(with = "as_decimal")]
+            ratio: AspectRatio,
+        }
+        let ratio = |r: &str| serde_json::from_value::<W>(serde_json::json!({ "ratio": r })).unwrap().ratio;
+
+        assert_eq!(ratio("1"), AspectRatio::new(1, 1));
+        assert_eq!(ratio("2"), AspectRatio::new(2, 1));
+        assert_eq!(ratio("0.5625"), AspectRatio::new(9, 16));
+        assert_eq!(ratio("1.78"), AspectRatio::new(89, 50));
+    }
+
+    #[test]
+    fn ratio_keywords() {
+        let wide = "2.33".parse::<AspectRatio>().unwrap();
+
+        assert_eq!("Ultrawide".parse::<RatioFilter>().unwrap(), RatioFilter::Ultrawide);
+        assert!(RatioFilter::Ultrawide.matches(&wide));
+        assert!(RatioFilter::Landscape.matches(&wide));
+        assert!(!RatioFilter::Portrait.matches(&wide));
+        assert_eq!(RatioFilter::Ultrawide.api_values(), ["21x9", "32x9", "48x9"]);
+    }
+}
@@ -0,0 +1,669 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use futures::TryFutureExt;
+use futures::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+use crate::api::{check_status, Wallpaper, WallhavenClient, WallhavenClientError};
+use crate::integrity::{check_file, Expected};
+use crate::query::FileType;
+
+
+fn file_style() -> ProgressStyle {
+    ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+        .unwrap()
+        .progress_chars("#>-")
+}
+
+fn overall_style() -> ProgressStyle {
+    ProgressStyle::with_template("{prefix:.bold} [{elapsed_precise}] [{wide_bar:.green}] {pos}/{len} wallpapers")
+        .unwrap()
+        .progress_chars("#>-")
+}
+
+
+impl WallhavenClient {
+    pub async fn download_image(&self, url: &str, path: &Path) -> Result<(), WallhavenClientError> {
+        let pb = ProgressBar::new(0);
+        pb.set_style(file_style());
+
+        self.download_image_with_progress(url, path, &pb).await
+    }
+
+    /// Download `url` into `path` reporting progress on `pb`
+    ///
+    /// The image is written to `<path>.part` and renamed to `path` once complete,
+    /// an existing `.part` file is resumed with a range request.
+    /// Anything but a complete jpg or png is rejected, see [`download_checked`](Self::download_checked).
+    /// Failed downloads are retried according to the client download retry policy.
+    pub async fn download_image_with_progress(&self, url: &str, path: &Path, pb: &ProgressBar) -> Result<(), WallhavenClientError> {
+        self.download_checked(url, path, Expected::default(), pb).await
+    }
+
+    /// Download `url` into `path` like [`download_image_with_progress`](Self::download_image_with_progress),
+    /// checking the image against `expected` before it gets its final name
+    ///
+    /// Truncated or wrongly sized images are retried according to the client
+    /// download retry policy, images of the wrong type fail with a decode error.
+    pub async fn download_checked(&self, url: &str, path: &Path, expected: Expected, pb: &ProgressBar) -> Result<(), WallhavenClientError> {
+        let url = &self.image_url(url);
+
+        self.download_retry.run(|| self.download_attempt(url, path, expected, pb)).await
+    }
+
+    async fn download_attempt(&self, url: &str, path: &Path, expected: Expected, pb: &ProgressBar) -> Result<(), WallhavenClientError> {
+        let part = part_path(path);
+
+        // Resume from a previous partial download
+        let offset = match tokio::fs::metadata(&part).await {
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        };
+
+        // Reqwest setup
+        let mut req = self.http_client.get(url);
+        if offset > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let res = req
+            .send()
+            .await
+            .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
+
+        if res.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // Partial doesn't match the remote file anymore, start over
+            remove_part(&part).await?;
+            return Box::pin(self.download_attempt(url, path, expected, pb)).await;
+        }
+        let res = check_status(res).await?;
+
+        //println!("{:#?}", res);
+
+        // Server may ignore the range and send the whole file
+        let resumed = res.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let offset = if resumed { offset } else { 0 };
+
+        // Get information for bar, some servers don't send the length
+        let total_size = res
+            .content_length()
+            .map(|len| len + offset)
+            .or(expected.size);
+
+        if let Some(total_size) = total_size {
+            pb.set_length(total_size);
+        }
+        pb.set_position(offset);
+        pb.set_message(format!("Downloading {}", url));
+
+        // Create file path, subdirectories of a layout may not exist yet
+        if let Some(dir) = part.parent().filter(|d| !d.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir)
+                .map_err(|e| WallhavenClientError::WriteError(format!("Failed to create {} - {}", dir.display(), e)))
+                .await?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(resumed)
+            .write(true)
+            .truncate(!resumed)
+            .open(&part)
+            .await
+            .map_err(|e| WallhavenClientError::WriteError(
+                    format!("Failed to create file - {}", e))
+                )?;
+
+
+        // Write file
+        let mut downloaded: u64 = offset;
+        let mut stream = res.bytes_stream();
+
+        while let Some(item) = stream.next().await {
+            let chunk = item
+                .or(Err(
+                    WallhavenClientError::RequestError(String::from("Error while downloading file"))
+                    ))?;
+
+            file.write_all(&chunk)
+                .map_err(|e| WallhavenClientError::WriteError(format!("Error while writing to file - {}", e)))
+                .await?;
+
+            downloaded += chunk.len() as u64;
+            pb.set_position(total_size.map_or(downloaded, |total| downloaded.min(total)));
+        }
+
+        file.flush()
+            .map_err(|e| WallhavenClientError::WriteError(format!("Error while writing to file - {}", e)))
+            .await?;
+        drop(file);
+
+        if let Err(problem) = check_file(&part, expected).await {
+            // A resumed partial would carry the problem over
+            remove_part(&part).await?;
+            let msg = format!("{} - {}", url, problem);
+            return Err(if problem.is_transient() {
+                WallhavenClientError::RequestError(msg)
+            } else {
+                WallhavenClientError::DecodeError(msg)
+            });
+        }
+
+        // Only complete images get the final name
+        tokio::fs::rename(&part, path)
+            .map_err(|e| WallhavenClientError::WriteError(format!("Failed to rename {} - {}", part.display(), e)))
+            .await?;
+
+        pb.finish_with_message(format!("Downloaded {}", url));
+
+        Ok(())
+    }
+}
+
+
+/// Path of the in progress download of `path`
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+
+    path.with_file_name(name)
+}
+
+/// Path of the json metadata file of the image at `path`, `<path>.json`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".json");
+
+    path.with_file_name(name)
+}
+
+async fn remove_part(part: &Path) -> Result<(), WallhavenClientError> {
+    tokio::fs::remove_file(part)
+        .await
+        .map_err(|e| WallhavenClientError::WriteError(format!("Failed to remove {} - {}", part.display(), e)))
+}
+
+
+/// A file to download
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub url: String,
+    pub path: PathBuf,
+    pub expected: Expected,
+    /// Wallpaper id to write the full info of next to the image, see [`sidecar_path`]
+    ///
+    /// The api has no endpoint for the info of several wallpapers, so each sidecar
+    /// is one request paced by the client rate limit along with the other api requests.
+    pub sidecar: Option<String>,
+}
+
+/// Outcome of a batch of downloads
+#[derive(Debug, Default)]
+pub struct DownloadReport {
+    pub downloaded: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, WallhavenClientError)>,
+}
+
+impl DownloadReport {
+    pub fn merge(&mut self, other: DownloadReport) {
+        self.downloaded.extend(other.downloaded);
+        self.skipped.extend(other.skipped);
+        self.failed.extend(other.failed);
+    }
+}
+
+enum Outcome {
+    Downloaded(PathBuf),
+    Skipped(PathBuf),
+    Failed(PathBuf, WallhavenClientError),
+}
+
+
+/// What to do when the target file already exists
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExistsPolicy {
+    /// Keep the existing file
+    #[default]
+    Skip,
+    /// Download again replacing the existing file
+    Overwrite,
+    /// Download to a free `<name>-<n>.<ext>` path
+    Rename,
+    /// Download again only if the existing file isn't the expected image
+    Verify,
+}
+
+impl FromStr for ExistsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            "verify" => Ok(Self::Verify),
+            _ => Err(format!("{s} is not a valid policy - skip, overwrite, rename or verify")),
+        }
+    }
+}
+
+/// Image of a wallpaper to download, the full one or a thumbnail
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    #[default]
+    Full,
+    ThumbSmall,
+    ThumbLarge,
+    ThumbOriginal,
+}
+
+impl Variant {
+    pub fn url<'w>(&self, wallpaper: &'w Wallpaper) -> &'w str {
+        match self {
+            Self::Full => &wallpaper.path,
+            Self::ThumbSmall => &wallpaper.thumbs.small,
+            Self::ThumbLarge => &wallpaper.thumbs.large,
+            Self::ThumbOriginal => &wallpaper.thumbs.original,
+        }
+    }
+
+    /// What the image should be, the size is only known for the full one
+    pub fn expected(&self, wallpaper: &Wallpaper) -> Expected {
+        match self {
+            Self::Full => Expected::of(wallpaper),
+            _ => Expected {
+                size: None,
+                file_type: Some(FileType::Jpg),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Full => "full",
+            Self::ThumbSmall => "thumb-small",
+            Self::ThumbLarge => "thumb-large",
+            Self::ThumbOriginal => "thumb-original",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "thumb-small" => Ok(Self::ThumbSmall),
+            "thumb-large" => Ok(Self::ThumbLarge),
+            "thumb-original" => Ok(Self::ThumbOriginal),
+            _ => Err(format!("{s} is not a valid variant - full, thumb-small, thumb-large or thumb-original")),
+        }
+    }
+}
+
+/// Path `download` should be saved to under `policy`, `None` to skip it
+async fn resolve_path(download: &Download, policy: ExistsPolicy) -> Option<PathBuf> {
+    let path = &download.path;
+    if tokio::fs::metadata(path).await.is_err() {
+        return Some(path.clone());
+    }
+
+    match policy {
+        ExistsPolicy::Skip => None,
+        ExistsPolicy::Overwrite => Some(path.clone()),
+        ExistsPolicy::Verify => check_file(path, download.expected).await
+            .err()
+            .map(|_| path.clone()),
+        ExistsPolicy::Rename => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+            for n in 1.. {
+                let candidate = path.with_file_name(format!("{stem}-{n}{ext}"));
+                if tokio::fs::metadata(&candidate).await.is_err() {
+                    return Some(candidate);
+                }
+            }
+            unreachable!()
+        },
+    }
+}
+
+
+/// Downloads batches of files with bounded concurrency
+///
+/// Shows one bar per active download plus an overall bar, which keeps
+/// growing across batches until [`Downloader::finish`].
+///
+/// Dropping the downloader mid batch (ex. on Ctrl-C) removes the `.part` files
+/// of the cancelled downloads, while failed downloads keep theirs to be resumed.
+#[derive(Debug)]
+pub struct Downloader<'a> {
+    client: &'a WallhavenClient,
+    concurrency: usize,
+    on_exists: ExistsPolicy,
+    progress: MultiProgress,
+    overall: ProgressBar,
+    in_flight: Mutex<HashSet<PathBuf>>,
+}
+
+impl<'a> Downloader<'a> {
+    pub fn new(client: &'a WallhavenClient) -> Self {
+        let progress = MultiProgress::new();
+        let overall = progress.add(ProgressBar::new(0));
+        overall.set_style(overall_style());
+        overall.set_prefix("Total");
+
+        Self {
+            client,
+            concurrency: 1,
+            on_exists: ExistsPolicy::default(),
+            progress,
+            overall,
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Download up to `concurrency` files at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// What to do with files that already exist, skip them by default
+    pub fn with_on_exists(mut self, on_exists: ExistsPolicy) -> Self {
+        self.on_exists = on_exists;
+        self
+    }
+
+    /// Download every file, a failed download doesn't stop the others
+    pub async fn download(&self, downloads: Vec<Download>) -> DownloadReport {
+        self.overall.inc_length(downloads.len() as u64);
+
+        let results: Vec<Outcome> = stream::iter(downloads)
+            .map(|d| async move {
+                let Some(path) = resolve_path(&d, self.on_exists).await else {
+                    // Sidecars may be asked for after the image was downloaded
+                    let res = match &d.sidecar {
+                        Some(id) if tokio::fs::metadata(sidecar_path(&d.path)).await.is_err() => self.write_sidecar(id, &d.path).await,
+                        _ => Ok(()),
+                    };
+                    self.overall.inc(1);
+                    return match res {
+                        Ok(()) => Outcome::Skipped(d.path),
+                        Err(e) => self.failed(d.path, e),
+                    };
+                };
+
+                let pb = self.progress.insert_before(&self.overall, ProgressBar::new(0));
+                pb.set_style(file_style());
+
+                self.in_flight.lock().unwrap().insert(path.clone());
+                let mut res = self.client.download_checked(&d.url, &path, d.expected, &pb).await;
+                self.in_flight.lock().unwrap().remove(&path);
+                if let (Ok(()), Some(id)) = (&res, &d.sidecar) {
+                    pb.set_message(format!("Fetching info of {}", id));
+                    res = self.write_sidecar(id, &path).await;
+                }
+
+                pb.finish_and_clear();
+                self.progress.remove(&pb);
+                self.overall.inc(1);
+
+                match res {
+                    Ok(()) => Outcome::Downloaded(path),
+                    Err(e) => self.failed(path, e),
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut report = DownloadReport::default();
+        for outcome in results {
+            match outcome {
+                Outcome::Downloaded(path) => report.downloaded.push(path),
+                Outcome::Skipped(path) => report.skipped.push(path),
+                Outcome::Failed(path, e) => report.failed.push((path, e)),
+            }
+        }
+
+        report
+    }
+
+    fn failed(&self, path: PathBuf, e: WallhavenClientError) -> Outcome {
+        self.println(format!("Failed {} - {}", path.display(), e));
+        Outcome::Failed(path, e)
+    }
+
+    /// Write the [`WallpaperInfo`](crate::api::WallpaperInfo) of wallpaper `id` next to the image at `path`
+    async fn write_sidecar(&self, id: &str, path: &Path) -> Result<(), WallhavenClientError> {
+        let info = self.client.wallpaper(id).await?;
+        let content = serde_json::to_string_pretty(&info)
+            .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+
+        // Written aside and renamed so an interrupted write doesn't pass for a sidecar
+        let sidecar = sidecar_path(path);
+        let part = part_path(&sidecar);
+        tokio::fs::write(&part, content)
+            .map_err(|e| WallhavenClientError::WriteError(format!("Failed to write {} - {}", part.display(), e)))
+            .await?;
+        tokio::fs::rename(&part, &sidecar)
+            .map_err(|e| WallhavenClientError::WriteError(format!("Failed to rename {} - {}", part.display(), e)))
+            .await
+    }
+
+    /// Print a line above the bars, or straight to stderr when they are hidden
+    fn println(&self, msg: String) {
+        if self.progress.is_hidden() {
+            eprintln!("{}", msg);
+        } else {
+            let _ = self.progress.println(msg);
+        }
+    }
+
+    pub fn finish(&self) {
+        self.overall.finish();
+    }
+}
+
+impl Drop for Downloader<'_> {
+    fn drop(&mut self) {
+        let in_flight = self.in_flight.get_mut().unwrap_or_else(|e| e.into_inner());
+
+        for path in in_flight.drain() {
+            let _ = std::fs::remove_file(part_path(&path));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Request, Response};
+    use crate::retry::RetryPolicy;
+
+    fn client() -> WallhavenClient {
+        WallhavenClient::new(None).unwrap()
+            .with_download_retry(RetryPolicy::with_retries(0))
+    }
+
+    /// Start of a `Range: bytes=<start>-` header
+    fn range_start(req: &Request) -> Option<usize> {
+        req.header("range")?.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok()
+    }
+
+    fn full(image: &[u8]) -> Response {
+        (200, vec![("Content-Type", String::from("image/jpeg"))], image.to_vec())
+    }
+
+    fn expected(image: &[u8]) -> Expected {
+        Expected { size: Some(image.len() as u64), file_type: Some(FileType::Jpg) }
+    }
+
+    #[test]
+    fn names_partial_downloads() {
+        assert_eq!(part_path(Path::new("/w/wallhaven-94x38z.jpg")), Path::new("/w/wallhaven-94x38z.jpg.part"));
+        assert_eq!(part_path(Path::new("a.b")), Path::new("a.b.part"));
+    }
+
+    #[test]
+    fn names_sidecars() {
+        assert_eq!(sidecar_path(Path::new("/w/wallhaven-94x38z.jpg")), Path::new("/w/wallhaven-94x38z.jpg.json"));
+    }
+
+    #[tokio::test]
+    async fn writes_sidecars_of_new_and_existing_images() {
+        let image = fixtures::jpg(100);
+        let served = image.clone();
+        let (url, requests) = fixtures::serve(move |req| match req.path.strip_prefix("/w/") {
+            Some("gone00") => (404, Vec::new(), br#"{"error": "Nothing here"}"#.to_vec()),
+            Some(id) => (200, Vec::new(), fixtures::wallpaper_info(id).to_string().into_bytes()),
+            None => full(&served),
+        }).await;
+        let client = client().with_base_url(&url).with_rate_limit(0);
+        let downloader = Downloader::new(&client);
+
+        let dir = fixtures::temp_dir("sidecars");
+        let download = |id: &str| Download {
+            url: format!("{url}/full/{id}.jpg"),
+            path: dir.join(format!("{id}.jpg")),
+            expected: expected(&image),
+            sidecar: Some(String::from(id)),
+        };
+        for id in ["exists", "gone00", "hasone"] {
+            std::fs::write(dir.join(format!("{id}.jpg")), &image).unwrap();
+        }
+        std::fs::write(dir.join("hasone.jpg.json"), "{}").unwrap();
+
+        let report = downloader.download(vec![download("fresh0"), download("exists"), download("gone00"), download("hasone")]).await;
+        assert_eq!(report.downloaded, [dir.join("fresh0.jpg")]);
+        let mut skipped = report.skipped.clone();
+        skipped.sort();
+        assert_eq!(skipped, [dir.join("exists.jpg"), dir.join("hasone.jpg")]);
+        // One missing info doesn't fail the others
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, dir.join("gone00.jpg"));
+
+        for id in ["fresh0", "exists"] {
+            let sidecar: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join(format!("{id}.jpg.json"))).unwrap()).unwrap();
+            assert_eq!(sidecar["id"], id);
+            assert_eq!(sidecar["tags"][0]["name"], "anime");
+        }
+        assert_eq!(std::fs::read_to_string(dir.join("hasone.jpg.json")).unwrap(), "{}");
+        assert!(!dir.join("gone00.jpg.json").exists());
+
+        let mut paths: Vec<_> = requests.lock().unwrap().iter().map(|r| r.path.clone()).collect();
+        paths.sort();
+        assert_eq!(paths, ["/full/fresh0.jpg", "/w/exists", "/w/fresh0", "/w/gone00"]);
+    }
+
+    #[tokio::test]
+    async fn resolves_existing_files() {
+        let dir = fixtures::temp_dir("resolve");
+        let image = fixtures::jpg(100);
+        let path = dir.join("a.jpg");
+        let download = Download {
+            url: String::new(),
+            path: path.clone(),
+            expected: expected(&image),
+            sidecar: None,
+        };
+
+        // Nothing there yet, every policy takes the path as is
+        for policy in [ExistsPolicy::Skip, ExistsPolicy::Overwrite, ExistsPolicy::Rename, ExistsPolicy::Verify] {
+            assert_eq!(resolve_path(&download, policy).await, Some(path.clone()));
+        }
+
+        std::fs::write(&path, &image).unwrap();
+        assert_eq!(resolve_path(&download, ExistsPolicy::Skip).await, None);
+        assert_eq!(resolve_path(&download, ExistsPolicy::Overwrite).await, Some(path.clone()));
+        assert_eq!(resolve_path(&download, ExistsPolicy::Verify).await, None);
+
+        assert_eq!(resolve_path(&download, ExistsPolicy::Rename).await, Some(dir.join("a-1.jpg")));
+        std::fs::write(dir.join("a-1.jpg"), &image).unwrap();
+        assert_eq!(resolve_path(&download, ExistsPolicy::Rename).await, Some(dir.join("a-2.jpg")));
+
+        // Same image under a different size
+        std::fs::write(&path, fixtures::jpg(90)).unwrap();
+        assert_eq!(resolve_path(&download, ExistsPolicy::Verify).await, Some(path.clone()));
+    }
+
+    #[tokio::test]
+    async fn resumes_a_partial_download() {
+        let image = fixtures::jpg(200);
+        let served = image.clone();
+        let (url, requests) = fixtures::serve(move |req| match range_start(req) {
+            Some(start) => (206, vec![("Content-Range", format!("bytes {}-{}/{}", start, served.len() - 1, served.len()))], served[start..].to_vec()),
+            None => full(&served),
+        }).await;
+
+        let dir = fixtures::temp_dir("resume");
+        let path = dir.join("a.jpg");
+        std::fs::write(part_path(&path), &image[..50]).unwrap();
+
+        client().download_checked(&format!("{url}/a.jpg"), &path, expected(&image), &ProgressBar::hidden()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), image);
+        assert!(!part_path(&path).exists());
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].path, "/a.jpg");
+        assert_eq!(requests[0].header("range"), Some("bytes=50-"));
+    }
+
+    #[tokio::test]
+    async fn restarts_when_the_range_is_ignored() {
+        let image = fixtures::jpg(200);
+        let served = image.clone();
+        let (url, _) = fixtures::serve(move |_| full(&served)).await;
+
+        let dir = fixtures::temp_dir("ignored-range");
+        let path = dir.join("a.jpg");
+        std::fs::write(part_path(&path), [0xAA; 50]).unwrap();
+
+        // A 200 is the whole file, the partial is overwritten rather than appended to
+        client().download_checked(&format!("{url}/a.jpg"), &path, expected(&image), &ProgressBar::hidden()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), image);
+    }
+
+    #[tokio::test]
+    async fn starts_over_when_the_range_is_unsatisfiable() {
+        let image = fixtures::jpg(200);
+        let served = image.clone();
+        let (url, requests) = fixtures::serve(move |req| match range_start(req) {
+            Some(_) => (416, Vec::new(), Vec::new()),
+            None => full(&served),
+        }).await;
+
+        let dir = fixtures::temp_dir("unsatisfiable-range");
+        let path = dir.join("a.jpg");
+        std::fs::write(part_path(&path), [0xAA; 300]).unwrap();
+
+        client().download_checked(&format!("{url}/a.jpg"), &path, expected(&image), &ProgressBar::hidden()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), image);
+        let ranges: Vec<_> = requests.lock().unwrap().iter().map(range_start).collect();
+        assert_eq!(ranges, [Some(300), None]);
+    }
+
+    #[tokio::test]
+    async fn drops_the_part_of_a_bad_download() {
+        let (url, _) = fixtures::serve(|_| (200, vec![("Content-Type", String::from("text/html"))], b"<!DOCTYPE html>".to_vec())).await;
+
+        let dir = fixtures::temp_dir("bad-download");
+        let path = dir.join("a.jpg");
+
+        let res = client().download_checked(&format!("{url}/a.jpg"), &path, Expected::default(), &ProgressBar::hidden()).await;
+        assert!(matches!(res, Err(WallhavenClientError::DecodeError(_))), "{:?}", res);
+        assert!(!path.exists());
+        assert!(!part_path(&path).exists());
+    }
+}
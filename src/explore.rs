@@ -0,0 +1,201 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Wallpaper, WallhavenClient, WallhavenClientError};
+use crate::filter::Filter;
+use crate::params::SearchParams;
+use crate::query::SearchQuery;
+
+
+/// Progress of an exploration, can be saved to resume it later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExploreState {
+    /// Wallpaper the exploration started from
+    pub root: String,
+    /// Wallpapers left to search similar ones of, with their depth
+    queue: VecDeque<(String, u32)>,
+    seen: HashSet<String>,
+    /// Wallpapers discovered so far, in discovery order
+    pub found: Vec<Wallpaper>,
+}
+
+impl ExploreState {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: String::from(root),
+            queue: VecDeque::from([(String::from(root), 0)]),
+            seen: HashSet::from([String::from(root)]),
+            found: Vec::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+
+/// Breadth-first crawl of `like:` searches from a wallpaper,
+/// see [`WallhavenClient::explore`]
+#[derive(Debug)]
+pub struct Explorer<'a> {
+    client: &'a WallhavenClient,
+    /// Search preferences of every `like:` search, the query is replaced
+    params: SearchParams,
+    depth: u32,
+    fan_out: usize,
+    filter: Option<Filter>,
+    state: ExploreState,
+}
+
+impl<'a> Explorer<'a> {
+    pub(crate) fn new(client: &'a WallhavenClient, id: &str, params: &SearchParams, depth: u32, fan_out: usize) -> Self {
+        Self {
+            client,
+            params: params.clone(),
+            depth,
+            fan_out,
+            filter: None,
+            state: ExploreState::new(id),
+        }
+    }
+
+    /// Only keep and follow wallpapers matching `filter`
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Resume from a saved `state` of the same root wallpaper
+    pub fn with_state(mut self, state: ExploreState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn state(&self) -> &ExploreState {
+        &self.state
+    }
+
+    /// Search wallpapers similar to the next queued one,
+    /// `None` once there is nothing left to explore
+    ///
+    /// Returns the wallpapers not seen before, at most `fan_out` of them
+    /// from the first page of results.
+    pub async fn step(&mut self) -> Result<Option<Vec<Wallpaper>>, WallhavenClientError> {
+        let Some((id, depth)) = self.state.queue.front().cloned() else {
+            return Ok(None);
+        };
+
+        let params = SearchParams {
+            query: Some(SearchQuery::like(&id)),
+            page: None,
+            ..self.params.clone()
+        };
+        let res = self.client.search(&params).await?;
+
+        Ok(Some(self.take_similar(depth, res.data)))
+    }
+
+    /// Keep the new wallpapers similar to the front of the queue, at `depth`,
+    /// queueing them to be explored in turn
+    fn take_similar(&mut self, depth: u32, similar: Vec<Wallpaper>) -> Vec<Wallpaper> {
+        let mut found = Vec::new();
+        for w in similar {
+            if found.len() >= self.fan_out {
+                break;
+            }
+            if self.state.seen.contains(&w.id) || !self.filter.as_ref().map(|f| f.matches(&w)).unwrap_or(true) {
+                continue;
+            }
+
+            self.state.seen.insert(w.id.clone());
+            if depth + 1 < self.depth {
+                self.state.queue.push_back((w.id.clone(), depth + 1));
+            }
+            found.push(w);
+        }
+
+        // Only dequeued once searched, so a failed step is retried on resume
+        self.state.queue.pop_front();
+        self.state.found.extend(found.iter().cloned());
+
+        found
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::wallpaper;
+
+    fn similar(ids: &[&str]) -> Vec<Wallpaper> {
+        ids.iter().map(|id| wallpaper(id)).collect()
+    }
+
+    /// Feed the results of `searches`, keyed by searched id, until done
+    fn explore(explorer: &mut Explorer, searches: &[(&str, &[&str])]) -> Vec<String> {
+        let mut searched = Vec::new();
+        while let Some((id, depth)) = explorer.state.queue.front().cloned() {
+            let results = searches.iter().find(|(s, _)| *s == id).map(|(_, r)| similar(r)).unwrap_or_default();
+            explorer.take_similar(depth, results);
+            searched.push(id);
+        }
+        searched
+    }
+
+    fn found<'a>(explorer: &'a Explorer<'_>) -> Vec<&'a str> {
+        explorer.state().found.iter().map(|w| w.id.as_str()).collect()
+    }
+
+    const SEARCHES: &[(&str, &[&str])] = &[
+        ("root00", &["aaaaaa", "bbbbbb", "cccccc"]),
+        ("aaaaaa", &["root00", "bbbbbb", "dddddd", "eeeeee"]),
+        ("bbbbbb", &["ffffff"]),
+        ("dddddd", &["gggggg"]),
+    ];
+
+    #[test]
+    fn explores_breadth_first_up_to_depth() {
+        let client = WallhavenClient::new(None).unwrap();
+
+        let mut explorer = client.explore("root00", &SearchParams::default(), 2, 2);
+        let searched = explore(&mut explorer, SEARCHES);
+        // Seen ones are skipped, the fan out counts new ones only
+        assert_eq!(searched, ["root00", "aaaaaa", "bbbbbb"]);
+        assert_eq!(found(&explorer), ["aaaaaa", "bbbbbb", "dddddd", "eeeeee", "ffffff"]);
+        assert!(explorer.state().is_done());
+
+        let mut explorer = client.explore("root00", &SearchParams::default(), 1, 5);
+        assert_eq!(explore(&mut explorer, SEARCHES), ["root00"]);
+        assert_eq!(found(&explorer), ["aaaaaa", "bbbbbb", "cccccc"]);
+    }
+
+    #[test]
+    fn filters_before_fan_out() {
+        let client = WallhavenClient::new(None).unwrap();
+
+        let mut explorer = client.explore("root00", &SearchParams::default(), 2, 1)
+            .with_filter("id!=aaaaaa".parse().unwrap());
+        assert_eq!(explore(&mut explorer, SEARCHES), ["root00", "bbbbbb"]);
+        assert_eq!(found(&explorer), ["bbbbbb", "ffffff"]);
+    }
+
+    #[test]
+    fn resumes_from_checkpoint() {
+        let client = WallhavenClient::new(None).unwrap();
+
+        let mut explorer = client.explore("root00", &SearchParams::default(), 3, 2);
+        explorer.take_similar(0, similar(SEARCHES[0].1));
+        let checkpoint = serde_json::to_string(explorer.state()).unwrap();
+
+        let state: ExploreState = serde_json::from_str(&checkpoint).unwrap();
+        let mut resumed = client.explore("root00", &SearchParams::default(), 3, 2).with_state(state);
+        assert_eq!(explore(&mut resumed, SEARCHES), ["aaaaaa", "bbbbbb", "dddddd", "eeeeee", "ffffff"]);
+
+        let mut uninterrupted = client.explore("root00", &SearchParams::default(), 3, 2);
+        explore(&mut uninterrupted, SEARCHES);
+        assert_eq!(found(&resumed), found(&uninterrupted));
+    }
+}
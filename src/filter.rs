@@ -0,0 +1,482 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::api::Wallpaper;
+use crate::color::Color;
+use crate::dimension::{AspectRatio, Resolution};
+use crate::flags::{Category, Purity};
+
+
+/// Client-side filter of search results
+///
+///    field op value - compare a wallpaper field
+///    a && b, a || b, !a, (a) - combine comparisons
+///
+///    ops: == != < <= > >=, ~ contains (text, colors) or is one of (purity, category)
+///    fields: views, favorites, file_size (1.5MB, 800KB), created_at (2024-01-01),
+///            width, height, resolution (1920x1080, by pixel count), ratio (16x9),
+///            purity, category, file_type (png/jpg), colors, id, source
+///
+/// Parsed with [`FromStr`], text values with spaces are quoted (`source~"art station"`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn matches(&self, wallpaper: &Wallpaper) -> bool {
+        self.expr.eval(wallpaper)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+
+/// Filter parse failure, `position` is the byte offset of the offending token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl FilterParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FilterParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FilterParseError {}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Id,
+    Views,
+    Favorites,
+    Source,
+    Purity,
+    Category,
+    Width,
+    Height,
+    Resolution,
+    Ratio,
+    FileSize,
+    FileType,
+    CreatedAt,
+    Colors,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i64),
+    /// `YYYY-MM-DD HH:MM:SS`, compared as text like the api gives it
+    Date(String),
+    Text(String),
+    Resolution(Resolution),
+    Ratio(AspectRatio),
+    Purity(Purity),
+    Category(Category),
+    Color(Color),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "id" => Self::Id,
+            "views" => Self::Views,
+            "favorites" => Self::Favorites,
+            "source" => Self::Source,
+            "purity" => Self::Purity,
+            "category" => Self::Category,
+            "width" | "dimension_x" => Self::Width,
+            "height" | "dimension_y" => Self::Height,
+            "resolution" => Self::Resolution,
+            "ratio" => Self::Ratio,
+            "file_size" | "size" => Self::FileSize,
+            "file_type" | "type" => Self::FileType,
+            "created_at" | "date" => Self::CreatedAt,
+            "colors" | "color" => Self::Colors,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    /// Operators that make sense for the field
+    fn allows(&self, op: Op) -> bool {
+        match self {
+            Self::Id | Self::Source | Self::FileType | Self::Purity | Self::Category => {
+                matches!(op, Op::Eq | Op::Ne | Op::Contains)
+            },
+            Self::Colors => op == Op::Contains,
+            _ => op != Op::Contains,
+        }
+    }
+
+    fn parse_value(&self, s: &str) -> Result<Value, String> {
+        let value = match self {
+            Self::Id | Self::Source => Value::Text(String::from(s)),
+            Self::Views | Self::Favorites | Self::Width | Self::Height => {
+                Value::Int(s.parse().map_err(|_| format!("{s} is not a number"))?)
+            },
+            Self::FileSize => Value::Int(parse_size(s)?),
+            Self::CreatedAt => Value::Date(parse_date(s)?),
+            Self::Resolution => Value::Resolution(s.parse()?),
+            Self::Ratio => Value::Ratio(s.parse()?),
+            Self::Purity => Value::Purity(s.parse()?),
+            Self::Category => Value::Category(s.parse()?),
+            Self::Colors => Value::Color(s.parse()?),
+            Self::FileType => {
+                let mime = match s.to_ascii_lowercase().as_str() {
+                    "png" => String::from("image/png"),
+                    "jpg" | "jpeg" => String::from("image/jpeg"),
+                    mime => String::from(mime),
+                };
+                Value::Text(mime)
+            },
+        };
+
+        Ok(value)
+    }
+
+    fn int(&self, w: &Wallpaper) -> i64 {
+        match self {
+            Self::Views => w.views as i64,
+            Self::Favorites => w.favorites as i64,
+            Self::Width => w.dimension_x as i64,
+            Self::Height => w.dimension_y as i64,
+            _ => w.file_size as i64,
+        }
+    }
+
+    fn text<'w>(&self, w: &'w Wallpaper) -> &'w str {
+        match self {
+            Self::Id => &w.id,
+            Self::Source => &w.source,
+            _ => &w.file_type,
+        }
+    }
+}
+
+impl Op {
+    fn test(&self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ne => ord != Ordering::Equal,
+            Self::Lt => ord == Ordering::Less,
+            Self::Le => ord != Ordering::Greater,
+            Self::Gt => ord == Ordering::Greater,
+            Self::Ge => ord != Ordering::Less,
+            Self::Contains => false,
+        }
+    }
+
+    /// `Eq`, `Ne` or `Contains` of values that aren't ordered
+    fn test_eq(&self, eq: bool, contains: bool) -> bool {
+        match self {
+            Self::Ne => !eq,
+            Self::Contains => contains,
+            _ => eq,
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, w: &Wallpaper) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(w) && b.eval(w),
+            Self::Or(a, b) => a.eval(w) || b.eval(w),
+            Self::Not(e) => !e.eval(w),
+            Self::Compare(field, op, value) => match value {
+                Value::Int(v) => op.test(field.int(w).cmp(v)),
+                Value::Date(d) => op.test(w.created_at.as_str().cmp(d.as_str())),
+                Value::Resolution(r) => op.test(w.resolution.cmp(r)),
+                Value::Ratio(r) => {
+                    let ord = if w.ratio.approx_eq(r) { Ordering::Equal } else { w.ratio.cmp(r) };
+                    op.test(ord)
+                },
+                Value::Text(t) => {
+                    let text = field.text(w);
+                    op.test_eq(text == t, text.contains(t.as_str()))
+                },
+                Value::Purity(p) => op.test_eq(w.purity == *p, w.purity.intersects(*p)),
+                Value::Category(c) => op.test_eq(w.category == *c, w.category.intersects(*c)),
+                Value::Color(c) => {
+                    let hex = format!("#{c}");
+                    w.colors.iter().any(|color| color.eq_ignore_ascii_case(&hex))
+                },
+            },
+        }
+    }
+}
+
+
+/// Bytes of `800KB`, `1.5MB`, `2G` or a plain number
+fn parse_size(s: &str) -> Result<i64, String> {
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let multiplier: f64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("Unknown size unit '{unit}' - use B, KB, MB or GB")),
+    };
+    let number: f64 = number.parse()
+        .map_err(|_| format!("{s} is not a valid size"))?;
+
+    Ok((number * multiplier) as i64)
+}
+
+/// `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` (or with a `T`), as `YYYY-MM-DD HH:MM:SS`
+fn parse_date(s: &str) -> Result<String, String> {
+    let s = s.replacen('T', " ", 1);
+    let full = if s.len() == 10 { format!("{s} 00:00:00") } else { s };
+
+    let valid = full.len() == 19 && full.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        10 => c == ' ',
+        13 | 16 => c == ':',
+        _ => c.is_ascii_digit(),
+    });
+
+    if valid {
+        Ok(full)
+    } else {
+        Err(format!("{full} is not a valid date - expected YYYY-MM-DD"))
+    }
+}
+
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterParseError> {
+        let mut lhs = self.and()?;
+        while self.eat("||") {
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterParseError> {
+        let mut lhs = self.unary()?;
+        while self.eat("&&") {
+            lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            if !self.eat(")") {
+                return Err(FilterParseError::new(start, "Unclosed parenthesis"));
+            }
+            return Ok(expr);
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FilterParseError> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        let name_len = self.rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        let name = &self.rest()[..name_len];
+        if name.is_empty() {
+            return Err(FilterParseError::new(start, "Expected a field name"));
+        }
+        let field = Field::from_name(&name.to_ascii_lowercase())
+            .ok_or_else(|| FilterParseError::new(start, format!("Unknown field '{name}'")))?;
+        self.pos += name_len;
+
+        self.skip_whitespace();
+        let op_start = self.pos;
+        let op = [
+            ("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge),
+            ("<", Op::Lt), (">", Op::Gt), ("~", Op::Contains), ("=", Op::Eq),
+        ]
+            .into_iter()
+            .find(|(token, _)| self.eat(token))
+            .map(|(_, op)| op)
+            .ok_or_else(|| FilterParseError::new(op_start, format!("Expected an operator after '{name}'")))?;
+        if !field.allows(op) {
+            return Err(FilterParseError::new(op_start, format!("Operator not supported by '{name}'")));
+        }
+
+        self.skip_whitespace();
+        let value_start = self.pos;
+        let text = match self.rest().strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')
+                    .ok_or_else(|| FilterParseError::new(value_start, "Unterminated quote"))?;
+                self.pos += end + 2;
+                &quoted[..end]
+            },
+            None => {
+                let len = self.rest()
+                    .find(|c: char| c.is_whitespace() || "()&|".contains(c))
+                    .unwrap_or(self.rest().len());
+                self.pos += len;
+                &self.s[value_start..self.pos]
+            },
+        };
+        if text.is_empty() {
+            return Err(FilterParseError::new(value_start, format!("Expected a value for '{name}'")));
+        }
+
+        let value = field.parse_value(text)
+            .map_err(|e| FilterParseError::new(value_start, e))?;
+
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        let expr = parser.or()?;
+
+        parser.skip_whitespace();
+        if let Some(c) = parser.rest().chars().next() {
+            return Err(FilterParseError::new(parser.pos, format!("Unexpected '{c}'")));
+        }
+
+        Ok(Self {
+            source: String::from(s.trim()),
+            expr,
+        })
+    }
+}
+
+/// Serialized as the source expression
+impl Serialize for Filter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::wallpaper;
+
+    fn matches(filter: &str) -> bool {
+        filter.parse::<Filter>().unwrap().matches(&wallpaper("94x38z"))
+    }
+
+    #[test]
+    fn compares_fields() {
+        assert!(matches("favorites>100 && file_size<5MB && created_at>2024-01-01"));
+        assert!(matches("resolution>=1920x1080 && ratio==16:9 && width==2560"));
+        assert!(matches("file_type==png && purity~sfw,sketchy && category!=people"));
+        assert!(matches("colors~0066cc && source~\"art station\" && id==94x38z"));
+        assert!(!matches("created_at>=2024-03-11"));
+        assert!(!matches("file_size>3.5MB"));
+    }
+
+    #[test]
+    fn combines_expressions() {
+        assert!(matches("views<100 || favorites>=120"));
+        assert!(matches("!(views<100 || type==jpg)"));
+        assert!(!matches("views>100 && !(purity==sfw)"));
+        // && binds tighter than ||
+        assert!(matches("views>1 || views<1 && views<1"));
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let err = "favorites>100 && stars>3".parse::<Filter>().unwrap_err();
+        assert_eq!(err.position, 17);
+
+        let err = "file_size<5XB".parse::<Filter>().unwrap_err();
+        assert_eq!(err.position, 10);
+
+        let err = "(views>1".parse::<Filter>().unwrap_err();
+        assert_eq!(err.position, 0);
+
+        assert!("colors>0066cc".parse::<Filter>().is_err());
+        assert!("views>1 views<2".parse::<Filter>().is_err());
+    }
+}
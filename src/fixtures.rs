@@ -0,0 +1,143 @@
+//! Api responses and a stub server shared by the unit tests
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::api::{SearchResponse, Wallpaper, WallpaperMeta};
+
+
+/// A 2560x1440 anime sfw png wallpaper uploaded on 2024-03-10 12:30:00
+pub(crate) fn wallpaper(id: &str) -> Wallpaper {
+    serde_json::from_value(serde_json::json!({
+        "id": id, "url": "", "short_url": "", "views": 1500, "favorites": 120,
+        "source": "https://example.com/art station:1", "purity": "sfw", "category": "anime",
+        "dimension_x": 2560, "dimension_y": 1440, "resolution": "2560x1440", "ratio": "1.78",
+        "file_size": 3_500_000, "file_type": "image/png", "created_at": "2024-03-10 12:30:00",
+        "colors": ["#0066cc", "#000000"], "path": format!("https://w.wallhaven.cc/full/94/wallhaven-{id}.png"),
+        "thumbs": {
+            "large": format!("https://th.wallhaven.cc/lg/94/{id}.jpg"),
+            "original": format!("https://th.wallhaven.cc/orig/94/{id}.jpg"),
+            "small": format!("https://th.wallhaven.cc/small/94/{id}.jpg"),
+        },
+    })).unwrap()
+}
+
+/// Body of the `/w/<id>` response for [`wallpaper`], with its uploader and tags
+pub(crate) fn wallpaper_info(id: &str) -> serde_json::Value {
+    let mut info = serde_json::to_value(wallpaper(id)).unwrap();
+    info["uploader"] = serde_json::json!({
+        "username": "uploader", "group": "User",
+        "avatar": {"200px": "", "128px": "", "32px": "", "20px": ""},
+    });
+    info["tags"] = serde_json::json!([{
+        "id": 1, "name": "anime", "alias": "", "category_id": 1, "category": "Anime & Manga",
+        "purity": "sfw", "created_at": "2015-01-01 00:00:00",
+    }]);
+
+    serde_json::json!({ "data": info })
+}
+
+/// Page `current_page` of `last_page` of a search
+pub(crate) fn page(data: Vec<Wallpaper>, current_page: i32, last_page: i32) -> SearchResponse {
+    SearchResponse {
+        meta: WallpaperMeta {
+            current_page,
+            last_page,
+            per_page: 24,
+            total: last_page * 24,
+            query: None,
+            seed: None,
+        },
+        data,
+    }
+}
+
+/// A jpg made of one APP0 segment of `len` bytes, that [`check_file`](crate::check_file) accepts
+pub(crate) fn jpg(len: u16) -> Vec<u8> {
+    let mut jpg = vec![0xFF, 0xD8, 0xFF, 0xE0];
+    jpg.extend((len + 2).to_be_bytes());
+    jpg.extend((0..len).map(|i| i as u8 & 0x7F));
+    jpg.extend([0xFF, 0xD9]);
+    jpg
+}
+
+/// Empty directory for a test, named after it
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wallhaven-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+
+/// Request received by a [`serve`] stub
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Status, extra headers and body of a [`serve`] stub response
+pub(crate) type Response = (u16, Vec<(&'static str, String)>, Vec<u8>);
+
+/// Answer requests on a local port with `handler`,
+/// returning the base url and the requests received so far
+pub(crate) async fn serve<F>(handler: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let handler = Arc::new(handler);
+
+    let received = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            // No request bodies, the head is all there is
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !head.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => head.extend(&buf[..n]),
+                }
+            }
+            let head = String::from_utf8_lossy(&head);
+            let mut lines = head.lines();
+            let path = lines.next().and_then(|l| l.split(' ').nth(1)).unwrap_or_default();
+            let request = Request {
+                path: String::from(path),
+                headers: lines
+                    .filter_map(|l| l.split_once(':'))
+                    .map(|(n, v)| (String::from(n.trim()), String::from(v.trim())))
+                    .collect(),
+            };
+
+            let (status, headers, body) = handler(&request);
+            received.lock().unwrap().push(request);
+
+            let mut res = format!("HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+            for (name, value) in headers {
+                res.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            res.push_str("\r\n");
+            let _ = stream.write_all(res.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (url, requests)
+}
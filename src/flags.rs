@@ -0,0 +1,197 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitflags::Flags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+
+bitflags::bitflags! {
+    /// Wallpaper categories, api order general/anime/people
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Category: u8 {
+        const GENERAL = 0b100;
+        const ANIME = 0b010;
+        const PEOPLE = 0b001;
+    }
+}
+
+bitflags::bitflags! {
+    /// Wallpaper purities, api order sfw/sketchy/nsfw
+    ///
+    /// *NSFW requires a valid API key*
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Purity: u8 {
+        const SFW = 0b100;
+        const SKETCHY = 0b010;
+        const NSFW = 0b001;
+    }
+}
+
+
+/// Parse the 3-bit api form (ex. `110`) or comma separated names (ex. `sfw,sketchy`)
+fn parse<F: Flags + Copy>(s: &str, kind: &str) -> Result<F, String> {
+    let flags: F = parse_any(s, kind)?;
+
+    if flags.is_empty() {
+        return Err(format!("At least one {kind} must be on"));
+    }
+
+    Ok(flags)
+}
+
+/// Same as [`parse`] allowing no flags
+fn parse_any<F: Flags + Copy>(s: &str, kind: &str) -> Result<F, String> {
+    let s = s.trim();
+    let mut flags = F::empty();
+
+    if s.len() == F::FLAGS.len() && s.chars().all(|c| c == '0' || c == '1') {
+        for (c, flag) in s.chars().zip(F::FLAGS) {
+            if c == '1' {
+                flags.insert(*flag.value());
+            }
+        }
+    } else {
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if name.eq_ignore_ascii_case("all") {
+                flags = F::all();
+                continue;
+            }
+
+            let flag = F::FLAGS.iter()
+                .find(|f| f.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    let names: Vec<String> = F::FLAGS.iter().map(|f| f.name().to_ascii_lowercase()).collect();
+                    format!("{name} is not a valid {kind} - use {} or the 3-bit form", names.join(", "))
+                })?;
+            flags.insert(*flag.value());
+        }
+    }
+
+    Ok(flags)
+}
+
+/// Lowercase names of the flags that are on
+fn names<F: Flags + Copy>(flags: &F) -> Vec<String> {
+    F::FLAGS.iter()
+        .filter(|f| flags.contains(*f.value()))
+        .map(|f| f.name().to_ascii_lowercase())
+        .collect()
+}
+
+/// 3-bit api form
+fn bits_string<F: Flags + Copy>(flags: &F) -> String {
+    F::FLAGS.iter()
+        .map(|f| if flags.contains(*f.value()) { '1' } else { '0' })
+        .collect()
+}
+
+
+/// Either a single string or a list of names
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    One(String),
+    List(Vec<String>),
+}
+
+fn deserialize_flags<'de, F: Flags + Copy, D: Deserializer<'de>>(deserializer: D, kind: &str) -> Result<F, D::Error> {
+    let s = match Repr::deserialize(deserializer)? {
+        Repr::One(s) => s,
+        Repr::List(l) => l.join(","),
+    };
+
+    parse_any(&s, kind).map_err(serde::de::Error::custom)
+}
+
+
+macro_rules! impl_flag_set {
+    ($ty:ty, $kind:literal) => {
+        impl $ty {
+            /// 3-bit api form (ex. `110`)
+            pub fn bits_string(&self) -> String {
+                bits_string(self)
+            }
+
+            /// Lowercase names of the flags that are on
+            pub fn names(&self) -> Vec<String> {
+                names(self)
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                parse(s, $kind)
+            }
+        }
+
+        /// Comma separated names (ex. `sfw,sketchy`)
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", names(self).join(","))
+            }
+        }
+
+        /// Serialized as its names (ex. `"sfw"`), see [`as_list`] for a list
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        /// Accepts a name, comma separated names, the 3-bit form or a list of names
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserialize_flags(deserializer, $kind)
+            }
+        }
+    };
+}
+
+impl_flag_set!(Category, "category");
+impl_flag_set!(Purity, "purity");
+
+
+/// Serialize flags as a list of names, as the api does for user settings
+pub mod as_list {
+    use super::*;
+
+    pub fn serialize<F: Flags + Copy, S: Serializer>(flags: &F, serializer: S) -> Result<S::Ok, S::Error> {
+        names(flags).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, F: Flags + Copy, D: Deserializer<'de>>(deserializer: D) -> Result<F, D::Error> {
+        deserialize_flags(deserializer, "flag")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bits_and_names() {
+        assert_eq!("011".parse::<Category>().unwrap(), Category::ANIME | Category::PEOPLE);
+        assert_eq!("anime, People".parse::<Category>().unwrap(), Category::ANIME | Category::PEOPLE);
+        assert_eq!("all".parse::<Purity>().unwrap(), Purity::all());
+        assert_eq!((Purity::SFW | Purity::SKETCHY).bits_string(), "110");
+    }
+
+    #[test]
+    fn rejects_unknown_and_empty() {
+        assert!("anime,cars".parse::<Category>().is_err());
+        assert!("000".parse::<Purity>().is_err());
+        assert!("1101".parse::<Purity>().is_err());
+    }
+
+    #[test]
+    fn deserializes_api_forms() {
+        let purity: Purity = serde_json::from_str(r#""sketchy""#).unwrap();
+        assert_eq!(purity, Purity::SKETCHY);
+
+        let categories: Category = serde_json::from_str(r#"["general", "anime"]"#).unwrap();
+        assert_eq!(categories, Category::GENERAL | Category::ANIME);
+    }
+}
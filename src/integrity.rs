@@ -0,0 +1,312 @@
+//! Checks that a downloaded file is the image it should be
+//!
+//! Servers and proxies may answer with an html error page or cut the
+//! transfer short, neither should end up saved as a wallpaper.
+
+use std::fmt;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader};
+
+use crate::api::Wallpaper;
+use crate::query::FileType;
+
+
+const JPEG_MAGIC: &[u8] = &[0xFF, 0xD8, 0xFF];
+/// Start of image, the marker before `JPEG_MAGIC`'s last byte
+const JPEG_SOI_LEN: usize = 2;
+const JPEG_EOI: u8 = 0xD9;
+const JPEG_SOS: u8 = 0xDA;
+const PNG_MAGIC: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const PNG_END: &[u8] = b"IEND";
+
+
+/// What a downloaded image should be, unknown parts aren't checked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Expected {
+    /// Size in bytes
+    pub size: Option<u64>,
+    pub file_type: Option<FileType>,
+}
+
+impl Expected {
+    /// The full image of `wallpaper`
+    pub fn of(wallpaper: &Wallpaper) -> Self {
+        Self::from_api(wallpaper.file_size, &wallpaper.file_type)
+    }
+
+    /// From the `file_size` and `file_type` fields of the api
+    pub fn from_api(file_size: i32, file_type: &str) -> Self {
+        Self {
+            size: u64::try_from(file_size).ok(),
+            file_type: FileType::from_mime(file_type),
+        }
+    }
+}
+
+
+/// Why a file isn't the expected image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Unreadable(String),
+    /// Neither a jpg nor a png, ex. an html error page
+    NotAnImage,
+    WrongType { expected: FileType, found: FileType },
+    /// Missing the end of the image
+    Truncated,
+    WrongSize { expected: u64, found: u64 },
+}
+
+impl Problem {
+    /// Whether downloading again may fix it, a wrong file would come back the same
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Truncated | Self::WrongSize { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable(e) => write!(f, "unreadable - {}", e),
+            Self::NotAnImage => write!(f, "not an image"),
+            Self::WrongType { expected, found } => write!(f, "{} image, expected {}", found, expected),
+            Self::Truncated => write!(f, "truncated"),
+            Self::WrongSize { expected, found } => write!(f, "{} bytes, expected {}", found, expected),
+        }
+    }
+}
+
+
+/// Image type of a file from its first bytes
+pub fn sniff(header: &[u8]) -> Option<FileType> {
+    if header.starts_with(JPEG_MAGIC) {
+        Some(FileType::Jpg)
+    } else if header.starts_with(PNG_MAGIC) {
+        Some(FileType::Png)
+    } else {
+        None
+    }
+}
+
+/// Whether the image read by `reader`, past its magic bytes, gets to its end marker
+///
+/// The segments or chunks are walked rather than looking at the last bytes,
+/// jpg files may carry vendor trailers of a few KB after the end of the image.
+async fn reaches_end<R: AsyncBufRead + Unpin>(file_type: FileType, reader: &mut R) -> io::Result<bool> {
+    let walked = match file_type {
+        FileType::Jpg => walk_jpeg(reader).await,
+        FileType::Png => walk_png(reader).await,
+    };
+
+    match walked {
+        Ok(()) => Ok(true),
+        // Cut short, or garbage where the rest of the image should be
+        Err(e) if matches!(e.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read jpg segments up to the end of image marker
+async fn walk_jpeg<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<()> {
+    let mut marker = next_marker(reader).await?;
+    loop {
+        match marker {
+            JPEG_EOI => return Ok(()),
+            // Standalone markers, no length
+            0x01 | 0xD0..=0xD7 => marker = next_marker(reader).await?,
+            _ => {
+                let len = reader.read_u16().await?;
+                skip(reader, u64::from(len.saturating_sub(2))).await?;
+
+                marker = if marker == JPEG_SOS {
+                    skip_scan(reader).await?
+                } else {
+                    next_marker(reader).await?
+                };
+            },
+        }
+    }
+}
+
+/// Marker code after a 0xFF, fill bytes skipped
+async fn next_marker<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<u8> {
+    if reader.read_u8().await? != 0xFF {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected a jpg marker"));
+    }
+    loop {
+        match reader.read_u8().await? {
+            0xFF => continue,
+            marker => return Ok(marker),
+        }
+    }
+}
+
+/// Skip the compressed data of a scan, returning the marker ending it
+///
+/// 0xFF in the data is followed by 0x00, restart markers belong to the scan.
+async fn skip_scan<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<u8> {
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let Some(i) = buf.iter().position(|b| *b == 0xFF) else {
+            let len = buf.len();
+            reader.consume(len);
+            continue;
+        };
+        reader.consume(i + 1);
+
+        let mut next = reader.read_u8().await?;
+        while next == 0xFF {
+            next = reader.read_u8().await?;
+        }
+        if !matches!(next, 0x00 | 0xD0..=0xD7) {
+            return Ok(next);
+        }
+    }
+}
+
+/// Read png chunks up to the IEND one
+async fn walk_png<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let len = reader.read_u32().await?;
+        let mut chunk_type = [0u8; 4];
+        reader.read_exact(&mut chunk_type).await?;
+        if chunk_type == PNG_END {
+            return Ok(());
+        }
+        // Data and crc
+        skip(reader, u64::from(len) + 4).await?;
+    }
+}
+
+async fn skip<R: AsyncRead + Unpin>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = tokio::io::copy(&mut reader.take(len), &mut tokio::io::sink()).await?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(())
+}
+
+/// Check the image at `path` against `expected`, returning its type
+pub async fn check_file(path: &Path, expected: Expected) -> Result<FileType, Problem> {
+    let unreadable = |e: std::io::Error| Problem::Unreadable(e.to_string());
+
+    let mut file = tokio::fs::File::open(path).await.map_err(unreadable)?;
+    let len = file.metadata().await.map_err(unreadable)?.len();
+
+    let mut header = [0u8; PNG_MAGIC.len()];
+    let read = file.read(&mut header).await.map_err(unreadable)?;
+    let file_type = sniff(&header[..read]).ok_or(Problem::NotAnImage)?;
+
+    if let Some(expected) = expected.file_type.filter(|t| *t != file_type) {
+        return Err(Problem::WrongType { expected, found: file_type });
+    }
+    match expected.size {
+        // Nothing else to check, and no need to read the whole file
+        Some(size) if size == len => return Ok(file_type),
+        // A short file is an interrupted transfer, anything else a different file
+        Some(size) if size > len => return Err(Problem::Truncated),
+        Some(size) => return Err(Problem::WrongSize { expected: size, found: len }),
+        None => {}
+    }
+
+    let magic_len = match file_type {
+        FileType::Jpg => JPEG_SOI_LEN,
+        FileType::Png => PNG_MAGIC.len(),
+    };
+    file.seek(SeekFrom::Start(magic_len as u64)).await.map_err(unreadable)?;
+    let mut reader = BufReader::new(file);
+    if !reaches_end(file_type, &mut reader).await.map_err(unreadable)? {
+        return Err(Problem::Truncated);
+    }
+
+    Ok(file_type)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_images() {
+        assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), Some(FileType::Jpg));
+        assert_eq!(sniff(PNG_MAGIC), Some(FileType::Png));
+        assert_eq!(sniff(b"<!DOCTYPE html>"), None);
+        assert_eq!(sniff(&[0xFF, 0xD8]), None);
+    }
+
+    /// Jpg with an exif thumbnail, a restart marker and stuffed bytes in its scan
+    fn jpg() -> Vec<u8> {
+        let mut jpg = vec![0xFF, 0xD8];
+        jpg.extend([0xFF, 0xE0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00]);
+        jpg.extend([0xFF, 0xE1, 0x00, 0x08, 0xFF, 0xD8, 0x00, 0x00, 0xFF, 0xD9]);
+        jpg.extend([0xFF, 0xDA, 0x00, 0x04, 0x01, 0x00]);
+        jpg.extend([0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56, 0xFF, 0xD9]);
+        jpg
+    }
+
+    fn png() -> Vec<u8> {
+        let mut png = PNG_MAGIC.to_vec();
+        png.extend([0, 0, 0, 2, b'I', b'D', b'A', b'T', 0x78, 0x9C, 0, 0, 0, 0]);
+        png.extend([0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+        png
+    }
+
+    async fn complete(file_type: FileType, image: &[u8]) -> bool {
+        let magic_len = if file_type == FileType::Jpg { JPEG_SOI_LEN } else { PNG_MAGIC.len() };
+        reaches_end(file_type, &mut &image[magic_len..]).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn walks_to_the_end_marker() {
+        let jpg = jpg();
+        assert!(complete(FileType::Jpg, &jpg).await);
+        // Cut in the scan, past the end marker of the thumbnail
+        assert!(!complete(FileType::Jpg, &jpg[..jpg.len() - 2]).await);
+        assert!(!complete(FileType::Jpg, &jpg[..14]).await);
+
+        let png = png();
+        assert!(complete(FileType::Png, &png).await);
+        assert!(!complete(FileType::Png, &png[..png.len() - 12]).await);
+    }
+
+    #[tokio::test]
+    async fn ignores_trailers() {
+        // Ex. Samsung SEFT blocks
+        let mut jpg = jpg();
+        jpg.extend(b"SEFH".repeat(1024));
+        jpg.extend([0xFF, 0xD8, 0xFF]);
+        assert!(complete(FileType::Jpg, &jpg).await);
+
+        let mut png = png();
+        png.extend([0u8; 64]);
+        assert!(complete(FileType::Png, &png).await);
+    }
+
+    #[tokio::test]
+    async fn trusts_a_matching_size_over_the_end_marker() {
+        let path = std::env::temp_dir().join(format!("wallhaven-integrity-{}.jpg", std::process::id()));
+        let jpg = jpg();
+        let cut = &jpg[..jpg.len() - 2];
+        tokio::fs::write(&path, cut).await.unwrap();
+        let len = cut.len() as u64;
+
+        let sized = Expected { size: Some(len), file_type: Some(FileType::Jpg) };
+        assert_eq!(check_file(&path, sized).await, Ok(FileType::Jpg));
+        assert_eq!(check_file(&path, Expected::default()).await, Err(Problem::Truncated));
+        let short = Expected { size: Some(len + 1), ..sized };
+        assert_eq!(check_file(&path, short).await, Err(Problem::Truncated));
+        let long = Expected { size: Some(len - 1), ..sized };
+        assert_eq!(check_file(&path, long).await, Err(Problem::WrongSize { expected: len - 1, found: len }));
+
+        tokio::fs::write(&path, &jpg).await.unwrap();
+        assert_eq!(check_file(&path, Expected::default()).await, Ok(FileType::Jpg));
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+//! A [WallHaven](https://wallhaven.cc) API client
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), wallhaven::WallhavenClientError> {
+//! use wallhaven::{SearchParams, WallhavenClient};
+//!
+//! let client = WallhavenClient::from_env()?;
+//! let params = SearchParams {
+//!     query: Some("+cat +funny".parse().unwrap()),
+//!     ..SearchParams::default()
+//! };
+//! let results = client.search(&params).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod api;
+pub mod color;
+pub mod dimension;
+pub mod download;
+pub mod explore;
+#[cfg(test)]
+mod fixtures;
+pub mod filter;
+pub mod integrity;
+pub mod flags;
+pub mod pager;
+pub mod params;
+pub mod query;
+pub mod retry;
+pub mod template;
+
+pub use api::{
+    get_key,
+    WallhavenClient, WallhavenClientError,
+    SearchResponse, Wallpaper, WallpaperMeta, MetaQuery, Thumbs,
+    WallpaperInfo, Uploader, Avatar, Tag, UserSettings, UserCollections,
+};
+pub use color::{Color, PALETTE};
+pub use dimension::{AspectRatio, RatioFilter, Resolution};
+pub use download::{part_path, sidecar_path, Download, DownloadReport, Downloader, ExistsPolicy, Variant};
+pub use explore::{ExploreState, Explorer};
+pub use filter::{Filter, FilterParseError};
+pub use flags::{Category, Purity};
+pub use integrity::{check_file, Expected, Problem};
+pub use pager::{SearchPager, SeenMark};
+pub use params::{CollectionParams, SearchParams, Seed};
+pub use query::{FileType, QueryParseError, SearchQuery, Term, TermKind};
+pub use retry::RetryPolicy;
+pub use template::{FileNamer, Template};
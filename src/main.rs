@@ -1,28 +1,435 @@
-mod api;
 mod args;
+mod config;
+mod saved;
+mod state;
 
-use api::WallhavenClient;
-use clap::Parser;
-use crate::args::CLIArgs;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{CommandFactory, ErrorKind, Parser};
+use wallhaven::api::{TagResponse, UserCollectionsResponse, UserSettingsResponse, WallpaperInfoResponse};
+use wallhaven::{check_file, get_key, CollectionParams, Download, DownloadReport, Downloader, ExistsPolicy, Expected, ExploreState, FileNamer, FileType, RetryPolicy, SearchPager, SearchParams, SearchResponse, Seed, Wallpaper, WallhavenClient, WallhavenClientError};
+use crate::args::{CLIArgs, CLICommands, DownloadArgs, ExploreArgs, VerifyArgs};
+use crate::config::Config;
+use crate::state::State;
 
 #[tokio::main]
-async fn main() -> Result<(), String> {
+async fn main() -> ExitCode {
+    // Config values are defaults for the cli arguments
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let config_path = config::global_value(&argv, "config").map(PathBuf::from);
+    let profile = config::global_value(&argv, "profile").map(|p| p.to_string_lossy().into_owned());
+    let config = match Config::load(config_path.as_deref(), profile.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
     // Get cli arguments
-    let args = CLIArgs::parse();
+    let mut args = CLIArgs::parse_from(config.apply(argv.clone()));
+
+    // Saved searches don't need the api, except when one is run
+    if let CLICommands::Saved(s) = &args.commands {
+        match saved::execute(&s.command, &argv, &config) {
+            Ok(saved::Saved::Search(search)) => args = *search,
+            Ok(saved::Saved::Output(output)) => {
+                println!("{}", output);
+                return ExitCode::SUCCESS;
+            },
+            Err(saved::Error::Usage(e)) => e.exit(),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
     //println!("{:#?}", args);
-    
-    // Create Api client
-    let wallhaven = WallhavenClient::new(args.commands)?;
 
-    // Execute request
-    let response = wallhaven.execute().await;
+    // Create Api client, environment api key wins over the config one
+    let api_key = get_key().ok().or(config.api_key);
+    let mut wallhaven = match WallhavenClient::new(api_key.as_deref()) {
+        Ok(c) => c.with_env(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return exit_code(&e);
+        }
+    };
+    if let Some(base_url) = &args.base_url {
+        wallhaven = wallhaven.with_base_url(base_url);
+    }
+    if let Some(image_host) = &args.image_host {
+        wallhaven = wallhaven.with_image_host(image_host);
+    }
+    let wallhaven = wallhaven
+        .with_rate_limit(args.rate_limit)
+        .with_api_retry(RetryPolicy::with_retries(args.retries))
+        .with_download_retry(RetryPolicy::with_retries(args.download_retries));
+
+    // Execute request, Ctrl-C drops it so unfinished downloads get cleaned up
+    let response = tokio::select! {
+        res = execute(&wallhaven, args.commands) => res,
+        _ = tokio::signal::ctrl_c() => Err(WallhavenClientError::Interrupted),
+    };
 
-    // IDK if I should handle the error like this
-    // since error can contains custom formated string not json
     match response {
-        Ok(res) => {println!("{}", res)},
-        Err(e) => {eprintln!("{}", e)},
+        Ok(res) => {
+            println!("{}", res);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            exit_code(&e)
+        },
+    }
+}
+
+/// Exit code for each error kind, so scripts can branch on failures
+///
+/// 2 is left out since clap exits with it on usage errors.
+fn exit_code(e: &WallhavenClientError) -> ExitCode {
+    let code = match e {
+        WallhavenClientError::RequestError(_) => 3,
+        WallhavenClientError::DecodeError(_) => 4,
+        WallhavenClientError::WriteError(_) => 5,
+        WallhavenClientError::VerifyFailed(_) => 6,
+        WallhavenClientError::ReadError(_) => 7,
+        WallhavenClientError::Unauthorized => 10,
+        WallhavenClientError::NotFound => 11,
+        WallhavenClientError::RateLimited { .. } => 12,
+        WallhavenClientError::Api { .. } => 13,
+        WallhavenClientError::Interrupted => 130,
+    };
+
+    ExitCode::from(code)
+}
+
+async fn execute(wallhaven: &WallhavenClient, commands: CLICommands) -> Result<String, WallhavenClientError> {
+    let resp = match commands {
+        CLICommands::Search(s) => {
+            for (color, snapped) in s.color_substitutions() {
+                eprintln!("Color {color} is not searchable, using nearest {snapped}");
+            }
+
+            let mut params = SearchParams::from(&s);
+            if params.is_random() {
+                let seed = params.seed.get_or_insert_with(Seed::random);
+                eprintln!("Seed: {}", seed);
+            }
+            let mut pager = wallhaven.search_pages(&params, s.paging.page_count(), s.paging.limit());
+            if let Some(filter) = &s.paging.filter {
+                pager = pager.with_filter(filter.clone());
+            }
+
+            let Some(key) = &s.since_last_run else {
+                return walk_pages(wallhaven, &mut pager, &s.download).await;
+            };
+
+            // Only wallpapers newer than the last run, which needs newest first results
+            if !params.is_newest_first() {
+                CLIArgs::command()
+                    .error(ErrorKind::ArgumentConflict, "--since-last-run needs results sorted by DATE_ADDED in DESC order")
+                    .exit();
+            }
+            let mut state = State::load()
+                .map_err(WallhavenClientError::ReadError)?;
+            if let Some(mark) = state.since(key) {
+                pager = pager.with_since(mark.clone());
+            }
+
+            let resp = walk_pages(wallhaven, &mut pager, &s.download).await?;
+
+            // Only once everything went fine, failed downloads are retried next time
+            if let Some(newest) = pager.newest() {
+                state.set_since(key, newest.clone());
+                state.save()
+                    .map_err(WallhavenClientError::WriteError)?;
+            }
+
+            resp
+        },
+        CLICommands::WallpaperInfo(w) => {
+            let data = wallhaven.wallpaper(&w.id).await?;
+
+            to_json(&WallpaperInfoResponse { data })?
+        },
+        CLICommands::TagInfo(t) => {
+            let data = wallhaven.tag(t.id).await?;
+
+            to_json(&TagResponse { data })?
+        },
+        CLICommands::UserSettings(_) => {
+            let data = wallhaven.settings().await?;
+
+            to_json(&UserSettingsResponse { data })?
+        },
+        CLICommands::UserCollections(uc) => {
+            let data = wallhaven.collections(uc.username.as_deref()).await?;
+
+            to_json(&UserCollectionsResponse { data })?
+        },
+        CLICommands::Saved(_) => unreachable!("saved searches are run as a search"),
+        CLICommands::Collection(c) => {
+            let params = CollectionParams::from(&c);
+            let mut pager = wallhaven.collection_pages(&params, c.paging.page_count(), c.paging.limit());
+            if let Some(filter) = &c.paging.filter {
+                pager = pager.with_filter(filter.clone());
+            }
+
+            walk_pages(wallhaven, &mut pager, &c.download).await?
+        },
+        CLICommands::Explore(e) => explore(wallhaven, &e).await?,
+        CLICommands::Verify(v) => verify(wallhaven, &v).await?,
+    };
+
+    Ok(resp)
+}
+
+/// Fetch every page, downloading its wallpapers into `--path` if given,
+/// otherwise returns the merged results as json
+async fn walk_pages(wallhaven: &WallhavenClient, pager: &mut SearchPager<'_>, args: &DownloadArgs) -> Result<String, WallhavenClientError> {
+    let downloader = Downloader::new(wallhaven)
+        .with_concurrency(args.jobs as usize)
+        .with_on_exists(args.on_exists());
+    let mut namer = args.namer();
+    let mut report = DownloadReport::default();
+    let mut searchresp: Option<SearchResponse> = None;
+
+    while let Some(page) = pager.next_page().await? {
+        //download wallpapers
+        if let Some(path) = &args.path {
+            report.merge(download_wallpapers(&downloader, &mut namer, &page.data, path, args).await);
+        }
+
+        match searchresp.as_mut() {
+            Some(r) => r.merge(page),
+            None => searchresp = Some(page),
+        }
+    }
+    downloader.finish();
+
+    if args.path.is_none() {
+        return to_json(&searchresp);
+    }
+
+    report_failures(report)?;
+    Ok(String::new())
+}
+
+/// Report failures once the whole batch is done, failing with the first one
+fn report_failures(mut report: DownloadReport) -> Result<(), WallhavenClientError> {
+    if !report.failed.is_empty() {
+        eprintln!("{} wallpapers downloaded, {} failed", report.downloaded.len(), report.failed.len());
+        let (_, e) = report.failed.swap_remove(0);
+        return Err(e);
     }
 
     Ok(())
 }
+
+async fn explore(wallhaven: &WallhavenClient, args: &ExploreArgs) -> Result<String, WallhavenClientError> {
+    let params = SearchParams::from(args);
+    let mut explorer = wallhaven.explore(&args.id, &params, args.depth, args.fan_out as usize);
+    if let Some(filter) = &args.filter {
+        explorer = explorer.with_filter(filter.clone());
+    }
+
+    // Resume an interrupted exploration
+    if let Some(state) = args.checkpoint.as_deref().map(read_checkpoint).transpose()?.flatten() {
+        if state.root != args.id {
+            CLIArgs::command()
+                .error(ErrorKind::ArgumentConflict, format!("--checkpoint is an exploration of {}, not {}", state.root, args.id))
+                .exit();
+        }
+        explorer = explorer.with_state(state);
+    }
+
+    let downloader = Downloader::new(wallhaven)
+        .with_concurrency(args.download.jobs as usize)
+        .with_on_exists(args.download.on_exists());
+    let mut namer = args.download.namer();
+    let mut report = DownloadReport::default();
+
+    // Wallpapers found before being interrupted, already downloaded ones are skipped
+    if let Some(path) = &args.download.path {
+        report.merge(download_wallpapers(&downloader, &mut namer, &explorer.state().found, path, &args.download).await);
+    }
+
+    while let Some(found) = explorer.step().await? {
+        if let Some(path) = &args.download.path {
+            report.merge(download_wallpapers(&downloader, &mut namer, &found, path, &args.download).await);
+        }
+        if let Some(checkpoint) = &args.checkpoint {
+            write_checkpoint(checkpoint, explorer.state())?;
+        }
+    }
+    downloader.finish();
+
+    // Kept if downloads failed, resuming retries them
+    report_failures(report)?;
+    if let Some(checkpoint) = &args.checkpoint {
+        let _ = std::fs::remove_file(checkpoint);
+    }
+
+    if args.download.path.is_some() {
+        return Ok(String::new());
+    }
+    to_json(&ExploreResponse { data: &explorer.state().found })
+}
+
+#[derive(serde::Serialize)]
+struct ExploreResponse<'a> {
+    data: &'a [Wallpaper],
+}
+
+/// Check every image under `dir`, printing the bad ones,
+/// fails if any is left bad
+async fn verify(wallhaven: &WallhavenClient, args: &VerifyArgs) -> Result<String, WallhavenClientError> {
+    let mut files = Vec::new();
+    image_files(&args.dir, &mut files)
+        .map_err(|e| WallhavenClientError::ReadError(format!("Failed to read {} - {}", args.dir.display(), e)))?;
+    files.sort();
+
+    let mut bad = Vec::new();
+    for path in files {
+        let id = wallpaper_id(&path);
+        let mut expected = Expected {
+            size: None,
+            file_type: path.extension().and_then(|e| FileType::from_extension(&e.to_string_lossy())),
+        };
+        // Kept for --refetch, so it isn't requested twice
+        let mut info = None;
+
+        if let Some(id) = id.as_deref().filter(|_| args.remote) {
+            match wallhaven.wallpaper(id).await {
+                Ok(found) => {
+                    expected = Expected::from_api(found.file_size, &found.file_type);
+                    info = Some(Ok(found));
+                },
+                // Named like one, but not a wallpaper
+                Err(WallhavenClientError::NotFound) => info = Some(Err(WallhavenClientError::NotFound)),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Err(problem) = check_file(&path, expected).await {
+            println!("{} - {}", path.display(), problem);
+            bad.push((path, id, info));
+        }
+    }
+
+    let mut left = bad.len();
+    if args.refetch && !bad.is_empty() {
+        let mut downloads = Vec::new();
+        for (path, id, info) in bad {
+            let Some(id) = id else {
+                eprintln!("Can't fetch {} again, it isn't named after a wallpaper id", path.display());
+                continue;
+            };
+            let info = match info {
+                Some(info) => info,
+                None => wallhaven.wallpaper(&id).await,
+            };
+            // One missing wallpaper doesn't stop the others
+            let info = match info {
+                Ok(info) => info,
+                Err(e) => {
+                    eprintln!("Can't fetch {} again - {}", path.display(), e);
+                    continue;
+                },
+            };
+
+            downloads.push(Download {
+                url: info.path,
+                path,
+                expected: Expected::from_api(info.file_size, &info.file_type),
+                sidecar: None,
+            });
+        }
+
+        let downloader = Downloader::new(wallhaven)
+            .with_concurrency(args.jobs as usize)
+            .with_on_exists(ExistsPolicy::Overwrite);
+        let report = downloader.download(downloads).await;
+        downloader.finish();
+
+        eprintln!("{} wallpapers fetched again", report.downloaded.len());
+        left -= report.downloaded.len();
+    }
+
+    if left > 0 {
+        return Err(WallhavenClientError::VerifyFailed(format!("{} bad wallpapers in {}", left, args.dir.display())));
+    }
+    Ok(String::new())
+}
+
+/// Images under `dir` and its subdirectories, in progress downloads left out
+fn image_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            image_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| FileType::from_extension(&e.to_string_lossy()).is_some()) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Id of the wallpaper a file is named after, `<id>.<ext>` or `wallhaven-<id>.<ext>`
+fn wallpaper_id(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+
+    args::valid_wallpaper_id(stem.strip_prefix("wallhaven-").unwrap_or(stem)).ok()
+}
+
+fn read_checkpoint(path: &Path) -> Result<Option<ExploreState>, WallhavenClientError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(WallhavenClientError::ReadError(format!("Failed to read checkpoint {} - {}", path.display(), e))),
+    };
+
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| WallhavenClientError::ReadError(format!("Invalid checkpoint {} - {}", path.display(), e)))
+}
+
+fn write_checkpoint(path: &Path, state: &ExploreState) -> Result<(), WallhavenClientError> {
+    let content = serde_json::to_string(state)
+        .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
+
+    config::write_file(path, &content)
+        .map_err(|e| WallhavenClientError::WriteError(format!("Failed to write checkpoint {} - {}", path.display(), e)))
+}
+
+async fn download_wallpapers(downloader: &Downloader<'_>, namer: &mut FileNamer, wallpapers: &[Wallpaper], path: &str, args: &DownloadArgs) -> DownloadReport {
+    let wallpaper_path = std::path::PathBuf::from(path);
+    let variant = args.variant();
+
+    let downloads = wallpapers.iter()
+        .map(|w| {
+            let (path, collided) = namer.path(&wallpaper_path, w);
+            if let Some(other) = collided {
+                eprintln!("{} and {} have the same name, saving {} as {}", other, w.id, w.id, path.display());
+            }
+
+            Download {
+                url: String::from(variant.url(w)),
+                path,
+                expected: variant.expected(w),
+                sidecar: args.sidecar.then(|| w.id.clone()),
+            }
+        })
+        .collect();
+
+    downloader.download(downloads).await
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, WallhavenClientError> {
+    serde_json::to_string(value)
+        .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::{SearchResponse, Wallpaper, WallhavenClient, WallhavenClientError};
+use crate::filter::Filter;
+use crate::params::{CollectionParams, SearchParams};
+
+
+/// What is being paged
+#[derive(Debug)]
+enum Source {
+    Search(Box<SearchParams>),
+    Collection(CollectionParams),
+}
+
+
+/// Newest wallpaper of a run, to only fetch newer ones the next time
+///
+/// Results must be sorted newest first (the default date added sorting).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeenMark {
+    pub id: String,
+    pub created_at: String,
+}
+
+impl SeenMark {
+    pub fn of(wallpaper: &Wallpaper) -> Self {
+        Self {
+            id: wallpaper.id.clone(),
+            created_at: wallpaper.created_at.clone(),
+        }
+    }
+
+    /// Whether `wallpaper` is this one or older
+    pub fn seen(&self, wallpaper: &Wallpaper) -> bool {
+        wallpaper.id == self.id || wallpaper.created_at < self.created_at
+    }
+}
+
+
+/// Walks the pages of a search or collection,
+/// see [`WallhavenClient::search_pages`] and [`WallhavenClient::collection_pages`]
+#[derive(Debug)]
+pub struct SearchPager<'a> {
+    client: &'a WallhavenClient,
+    source: Source,
+    /// Pages left to fetch, `None` walks until the last page
+    pages_left: Option<u32>,
+    /// Wallpapers left to fetch, `None` for no limit
+    remaining: Option<usize>,
+    /// Drops the wallpapers not matching, before the limit is counted
+    filter: Option<Filter>,
+    /// Stops at the first wallpaper already seen
+    since: Option<SeenMark>,
+    /// Newest wallpaper fetched, filtered out or not
+    newest: Option<SeenMark>,
+    /// Paging reached the seen mark or the last page
+    complete: bool,
+    done: bool,
+}
+
+impl<'a> SearchPager<'a> {
+    pub(crate) fn search(client: &'a WallhavenClient, params: &SearchParams, pages: Option<u32>, limit: Option<usize>) -> Self {
+        Self::new(client, Source::Search(Box::new(params.clone())), pages, limit)
+    }
+
+    pub(crate) fn collection(client: &'a WallhavenClient, params: &CollectionParams, pages: Option<u32>, limit: Option<usize>) -> Self {
+        Self::new(client, Source::Collection(params.clone()), pages, limit)
+    }
+
+    fn new(client: &'a WallhavenClient, source: Source, pages: Option<u32>, limit: Option<usize>) -> Self {
+        Self {
+            client,
+            source,
+            pages_left: pages,
+            remaining: limit,
+            filter: None,
+            since: None,
+            newest: None,
+            complete: false,
+            done: false,
+        }
+    }
+
+    /// Only yield wallpapers matching `filter`, pages may come out empty
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Only yield wallpapers newer than `mark`, paging until the first one seen
+    /// whatever the page count
+    pub fn with_since(mut self, mark: SeenMark) -> Self {
+        self.since = Some(mark);
+        self.pages_left = None;
+        self
+    }
+
+    /// Newest wallpaper fetched so far, the mark for the next run
+    ///
+    /// `None` while a limit stopped paging before the seen mark,
+    /// moving the mark would skip the wallpapers in between for good.
+    pub fn newest(&self) -> Option<&SeenMark> {
+        self.newest.as_ref()
+            .filter(|_| self.since.is_none() || self.complete)
+    }
+
+    /// Fetch the next page, `None` once the pages, the limit or the results are exhausted
+    pub async fn next_page(&mut self) -> Result<Option<SearchResponse>, WallhavenClientError> {
+        if self.done || self.pages_left == Some(0) || self.remaining == Some(0) {
+            return Ok(None);
+        }
+
+        let res = match &self.source {
+            Source::Search(params) => self.client.search(params).await?,
+            Source::Collection(params) => self.client.collection(params).await?,
+        };
+
+        Ok(Some(self.take_page(res)))
+    }
+
+    /// Apply the since mark, filter and limit to a fetched page,
+    /// and move on to the next one
+    fn take_page(&mut self, mut res: SearchResponse) -> SearchResponse {
+        // Last page check needs the unfiltered results
+        let empty = res.data.is_empty();
+
+        let mut reached_seen = false;
+        if let Some(i) = self.since.as_ref().and_then(|since| res.data.iter().position(|w| since.seen(w))) {
+            res.data.truncate(i);
+            reached_seen = true;
+        }
+        for w in &res.data {
+            if self.newest.as_ref().map(|n| w.created_at > n.created_at).unwrap_or(true) {
+                self.newest = Some(SeenMark::of(w));
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            res.data.retain(|w| filter.matches(w));
+        }
+        if let Some(remaining) = self.remaining.as_mut() {
+            res.data.truncate(*remaining);
+            *remaining -= res.data.len();
+        }
+        if let Some(pages_left) = self.pages_left.as_mut() {
+            *pages_left -= 1;
+        }
+
+        let page = res.meta.current_page.max(1) as u32;
+        if empty || reached_seen || page >= res.meta.last_page.max(0) as u32 {
+            self.complete = true;
+            self.done = true;
+        }
+        match &mut self.source {
+            Source::Search(params) => {
+                params.page = Some(page + 1);
+                // Keep the order of random results for the next pages
+                if params.seed.is_none() {
+                    params.seed = res.meta.seed.as_deref().and_then(|s| s.parse().ok());
+                }
+            },
+            Source::Collection(params) => params.page = Some(page + 1),
+        }
+
+        res
+    }
+
+    /// Fetch every remaining page, merging the results
+    ///
+    /// The merged response keeps the meta of the last fetched page.
+    pub async fn collect(mut self) -> Result<Option<SearchResponse>, WallhavenClientError> {
+        let mut merged: Option<SearchResponse> = None;
+
+        while let Some(res) = self.next_page().await? {
+            match merged.as_mut() {
+                Some(m) => m.merge(res),
+                None => merged = Some(res),
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn wallpaper(id: &str, day: u32) -> Wallpaper {
+        Wallpaper {
+            created_at: format!("2024-01-{day:02} 10:00:00"),
+            ..fixtures::wallpaper(id)
+        }
+    }
+
+    fn ids(res: &SearchResponse) -> Vec<&str> {
+        res.data.iter().map(|w| w.id.as_str()).collect()
+    }
+
+    fn mark(id: &str, day: u32) -> SeenMark {
+        SeenMark::of(&wallpaper(id, day))
+    }
+
+    #[test]
+    fn truncates_at_seen_mark() {
+        let client = WallhavenClient::new(None).unwrap();
+
+        let mut pager = client.search_pages(&SearchParams::default(), None, None)
+            .with_since(mark("cccccc", 3));
+        let page = pager.take_page(fixtures::page(vec![wallpaper("eeeeee", 5), wallpaper("dddddd", 4), wallpaper("cccccc", 3), wallpaper("bbbbbb", 2)], 1, 3));
+        assert_eq!(ids(&page), ["eeeeee", "dddddd"]);
+        assert!(pager.done);
+
+        // The marked wallpaper may be gone, older ones stop it too
+        let mut pager = client.search_pages(&SearchParams::default(), None, None)
+            .with_since(mark("cccccc", 3));
+        let page = pager.take_page(fixtures::page(vec![wallpaper("eeeeee", 5), wallpaper("bbbbbb", 2)], 1, 3));
+        assert_eq!(ids(&page), ["eeeeee"]);
+        assert!(pager.done);
+    }
+
+    #[test]
+    fn keeps_paging_until_seen() {
+        let client = WallhavenClient::new(None).unwrap();
+        let mut pager = client.search_pages(&SearchParams::default(), None, None)
+            .with_since(mark("aaaaaa", 1));
+
+        let page = pager.take_page(fixtures::page(vec![wallpaper("ffffff", 6), wallpaper("eeeeee", 5)], 1, 3));
+        assert_eq!(ids(&page), ["ffffff", "eeeeee"]);
+        assert!(!pager.done);
+
+        let page = pager.take_page(fixtures::page(vec![wallpaper("dddddd", 4), wallpaper("aaaaaa", 1)], 2, 3));
+        assert_eq!(ids(&page), ["dddddd"]);
+        assert!(pager.done);
+        assert_eq!(pager.newest(), Some(&mark("ffffff", 6)));
+    }
+
+    #[test]
+    fn tracks_newest_before_filter_and_limit() {
+        let client = WallhavenClient::new(None).unwrap();
+        let mut pager = client.search_pages(&SearchParams::default(), None, Some(1))
+            .with_filter("id!=ffffff".parse().unwrap());
+
+        let page = pager.take_page(fixtures::page(vec![wallpaper("ffffff", 6), wallpaper("eeeeee", 5), wallpaper("dddddd", 4)], 1, 3));
+        assert_eq!(ids(&page), ["eeeeee"]);
+        assert_eq!(pager.newest(), Some(&mark("ffffff", 6)));
+        assert_eq!(pager.remaining, Some(0));
+    }
+
+    #[test]
+    fn pages_until_seen_whatever_the_page_count() {
+        let client = WallhavenClient::new(None).unwrap();
+        let mut pager = client.search_pages(&SearchParams::default(), Some(1), None)
+            .with_since(mark("aaaaaa", 1));
+
+        pager.take_page(fixtures::page(vec![wallpaper("ffffff", 6), wallpaper("eeeeee", 5)], 1, 3));
+        assert!(!pager.done);
+        assert_eq!(pager.pages_left, None);
+    }
+
+    #[test]
+    fn keeps_the_mark_when_limit_stops_before_seen() {
+        let client = WallhavenClient::new(None).unwrap();
+        let mut pager = client.search_pages(&SearchParams::default(), None, Some(3))
+            .with_since(mark("aaaaaa", 1));
+
+        let page = pager.take_page(fixtures::page(vec![wallpaper("ffffff", 6), wallpaper("eeeeee", 5), wallpaper("dddddd", 4), wallpaper("cccccc", 3)], 1, 3));
+        assert_eq!(ids(&page), ["ffffff", "eeeeee", "dddddd"]);
+        assert_eq!(pager.remaining, Some(0));
+        // cccccc wasn't delivered, the next run must still get it
+        assert_eq!(pager.newest(), None);
+
+        // Reaching the mark within the limit moves it
+        let mut pager = client.search_pages(&SearchParams::default(), None, Some(3))
+            .with_since(mark("dddddd", 4));
+        pager.take_page(fixtures::page(vec![wallpaper("ffffff", 6), wallpaper("eeeeee", 5), wallpaper("dddddd", 4)], 1, 3));
+        assert_eq!(pager.newest(), Some(&mark("ffffff", 6)));
+    }
+
+    #[test]
+    fn newest_ignores_the_seen_ones() {
+        let client = WallhavenClient::new(None).unwrap();
+        let mut pager = client.search_pages(&SearchParams::default(), None, None)
+            .with_since(mark("eeeeee", 5));
+
+        let page = pager.take_page(fixtures::page(vec![wallpaper("eeeeee", 5), wallpaper("dddddd", 4)], 1, 3));
+        assert!(page.data.is_empty());
+        assert!(pager.done);
+        // Nothing new, the stored mark is kept
+        assert_eq!(pager.newest(), None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::api::Url;
+use crate::color::Color;
+use crate::dimension::{RatioFilter, Resolution};
+use crate::flags::{Category, Purity};
+use crate::query::SearchQuery;
+
+
+/// Parameters of a wallpaper search
+///
+/// Every field is optional, unset fields are left to the api defaults
+/// (or the account preferences when using an api key).
+#[derive(Debug, Default, Clone)]
+pub struct SearchParams {
+    pub query: Option<SearchQuery>,
+    pub categories: Option<Category>,
+    pub purity: Option<Purity>,
+    pub sorting: Option<String>,
+    pub order: Option<String>,
+    pub toprange: Option<String>,
+    pub atleast: Option<Resolution>,
+    pub resolutions: Vec<Resolution>,
+    pub ratios: Vec<RatioFilter>,
+    /// Sent as given, see [`Color::snap`] for colors the api indexes
+    pub colors: Vec<Color>,
+    pub page: Option<u32>,
+    pub seed: Option<Seed>,
+}
+
+
+/// Parameters of a collection listing
+#[derive(Debug, Default, Clone)]
+pub struct CollectionParams {
+    pub username: String,
+    pub id: i32,
+    pub purity: Option<Purity>,
+    pub page: Option<u32>,
+}
+
+
+/// Seed of random results, `[a-zA-Z0-9]{6}`
+///
+/// The same seed gives the same order, so later pages don't repeat wallpapers.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Seed(String);
+
+impl Seed {
+    /// New random seed, generated client-side
+    pub fn random() -> Self {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+        // Randomly keyed by the standard library, no need for a rng crate
+        let mut n = RandomState::new().build_hasher().finish();
+        let seed = (0..6)
+            .map(|_| {
+                let c = CHARS[(n % CHARS.len() as u64) as usize] as char;
+                n /= CHARS.len() as u64;
+                c
+            })
+            .collect();
+
+        Seed(seed)
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 6 && s.chars().all(|c| c.is_ascii_alphabetic() || c.is_ascii_digit()) {
+            Ok(Seed(String::from(s)))
+        } else {
+            Err(format!("{s} is an invalid seed"))
+        }
+    }
+}
+
+
+impl SearchParams {
+    /// Whether results are sorted randomly, so pages need a [`Seed`]
+    pub fn is_random(&self) -> bool {
+        self.sorting.as_deref().map(|s| s.eq_ignore_ascii_case("random")).unwrap_or(false)
+    }
+
+    /// Whether results come newest first, as with the api defaults
+    pub fn is_newest_first(&self) -> bool {
+        let date_added = self.sorting.as_deref().map(|s| s.eq_ignore_ascii_case("date_added")).unwrap_or(true);
+        let desc = self.order.as_deref().map(|o| o.eq_ignore_ascii_case("desc")).unwrap_or(true);
+
+        date_added && desc
+    }
+
+    /// Query string parameters, before encoding
+    pub fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::<(&'static str, String)>::new();
+
+        // Search
+        if let Some(q) = &self.query {
+            params.push(("q", q.to_string()));
+        }
+        if !self.colors.is_empty() {
+            let colors: Vec<String> = self.colors.iter().map(Color::to_string).collect();
+            params.push(("colors", colors.join(",")));
+        }
+
+        // Search preferences
+        if let Some(categories) = &self.categories {
+            params.push(("categories", categories.bits_string()));
+        }
+        if let Some(purity) = &self.purity{
+            params.push(("purity", purity.bits_string()));
+        }
+        if let Some(page) = self.page {
+            params.push(("page", page.to_string()));
+        }
+        if let Some(seed) = &self.seed {
+            params.push(("seed", seed.0.clone()));
+        }
+
+        // Sorting
+        if let Some(order) = &self.order {
+            params.push(("order", order.to_ascii_lowercase()));
+        }
+        if let Some(sorting) = &self.sorting{
+            params.push(("sorting", sorting.to_ascii_lowercase()));
+        }
+        if let Some(toprange) = &self.toprange{
+            params.push(("topRange", toprange.to_ascii_lowercase()));
+        }
+
+        // Wallpaper Preferences
+        if let Some(atleast) = &self.atleast {
+            params.push(("atleast", atleast.to_string()));
+        }
+        if !self.resolutions.is_empty() {
+            let resolutions: Vec<String> = self.resolutions.iter().map(Resolution::to_string).collect();
+            params.push(("resolutions", resolutions.join(",")));
+        }
+        if !self.ratios.is_empty() {
+            let ratios: Vec<String> = self.ratios.iter().flat_map(RatioFilter::api_values).collect();
+            params.push(("ratios", ratios.join(",")));
+        }
+
+        params
+    }
+}
+
+impl Url for SearchParams {
+    fn to_url(&self, base_url: &str) -> String {
+        format!("{base_url}/search?{}", encode_query(&self.query_pairs()))
+    }
+}
+
+
+impl Url for CollectionParams {
+    fn to_url(&self, base_url: &str) -> String {
+        let mut params = Vec::<(&'static str, String)>::new();
+
+        if let Some(purity) = &self.purity{
+            params.push(("purity", purity.bits_string()));
+        }
+        if let Some(page) = self.page {
+            params.push(("page", page.to_string()));
+        }
+
+        format!("{base_url}/collections/{}/{}?{}", encode_segment(&self.username), self.id, encode_query(&params))
+    }
+}
+
+
+/// Percent encoded path segment,
+/// so `/`, `?`, `#` and spaces in ex. usernames don't change the url
+pub(crate) fn encode_segment(segment: &str) -> String {
+    let mut url = url::Url::parse("http://localhost/").expect("valid url");
+    url.path_segments_mut().expect("base url").pop().push(segment);
+
+    String::from(&url.path()[1..])
+}
+
+/// `application/x-www-form-urlencoded` query string,
+/// so `+`, `#`, `&` and non ascii characters reach the api as they are
+fn encode_query(pairs: &[(&str, String)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoded value of `key` in the query string of `url`
+    fn query_value(url: &str, key: &str) -> Option<String> {
+        let (_, query) = url.split_once('?')?;
+
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    }
+
+    fn search_url(query: &str) -> String {
+        let params = SearchParams {
+            query: Some(query.parse().unwrap()),
+            ..SearchParams::default()
+        };
+
+        params.to_url("https://wallhaven.cc/api/v1")
+    }
+
+    #[test]
+    fn required_and_excluded_tags_round_trip() {
+        let url = search_url("+cat +funny -dog");
+
+        assert!(!url.contains("+cat"), "raw + sent in {url}");
+        assert_eq!(query_value(&url, "q").as_deref(), Some("+cat +funny -dog"));
+    }
+
+    #[test]
+    fn username_and_like_round_trip() {
+        let url = search_url("anime @some_user like:94x38z");
+
+        assert_eq!(query_value(&url, "q").as_deref(), Some("anime @some_user like:94x38z"));
+    }
+
+    #[test]
+    fn unicode_tags_round_trip() {
+        let url = search_url("+猫 café");
+
+        assert!(url.is_ascii());
+        assert_eq!(query_value(&url, "q").as_deref(), Some("+猫 café"));
+    }
+
+    #[test]
+    fn reserved_characters_are_escaped() {
+        let url = search_url("c# r&b");
+
+        assert_eq!(query_value(&url, "q").as_deref(), Some("c# r&b"));
+        assert_eq!(query_value(&url, "purity"), None);
+    }
+
+    #[test]
+    fn exact_tag_id() {
+        let url = search_url("id:123");
+
+        assert_eq!(query_value(&url, "q").as_deref(), Some("id:123"));
+    }
+
+    #[test]
+    fn usernames_are_one_path_segment() {
+        let params = CollectionParams {
+            username: String::from("some user?#/x%"),
+            id: 12,
+            ..CollectionParams::default()
+        };
+
+        assert_eq!(params.to_url("https://wallhaven.cc/api/v1"), "https://wallhaven.cc/api/v1/collections/some%20user%3F%23%2Fx%25/12?");
+        assert_eq!(encode_segment("some_user"), "some_user");
+        assert_eq!(encode_segment("猫"), "%E7%8C%AB");
+    }
+
+    #[test]
+    fn random_seed_is_valid() {
+        let seed = Seed::random();
+
+        assert_eq!(seed.to_string().parse::<Seed>().unwrap(), seed);
+    }
+}
@@ -0,0 +1,389 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+
+/// How a term matches wallpaper tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermKind {
+    /// `tag` - search fuzzily for a tag/keyword
+    Fuzzy,
+    /// `+tag` - must have the tag
+    Required,
+    /// `-tag` - must NOT have the tag
+    Excluded,
+}
+
+/// A tag or keyword of the query, `text` may have spaces (`+"blue sky"`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub kind: TermKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Png,
+    Jpg,
+}
+
+impl FileType {
+    /// From a mime type, ex. `image/jpeg`
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime.to_ascii_lowercase().as_str() {
+            "image/png" => Some(Self::Png),
+            "image/jpeg" | "image/jpg" => Some(Self::Jpg),
+            _ => None,
+        }
+    }
+
+    /// From a file extension, ex. `jpeg`
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpg),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Png => write!(f, "png"),
+            Self::Jpg => write!(f, "jpg"),
+        }
+    }
+}
+
+
+/// Search query
+///
+///    tagname - search fuzzily for a tag/keyword
+///    -tagname - exclude a tag/keyword
+///    +tag1 +tag2 - must have tag1 and tag2
+///    +"tag name" - tags with spaces are quoted
+///    @username - user uploads
+///    id:123 - Exact tag search (can not be combined)
+///    type:{png/jpg} - Search for file type (jpg = jpeg)
+///    like:wallpaper ID - Find wallpapers with similar tags
+///
+/// Parsed with [`FromStr`] and serialized back to its canonical form with [`fmt::Display`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+    pub username: Option<String>,
+    /// Exact tag id, can't be combined with anything else
+    pub id: Option<u64>,
+    pub filetype: Option<FileType>,
+    /// Wallpaper id to find similar wallpapers to
+    pub like: Option<String>,
+}
+
+impl SearchQuery {
+    /// Query of wallpapers similar to wallpaper `id`
+    pub fn like(id: &str) -> Self {
+        Self {
+            like: Some(String::from(id)),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+
+/// Query parse failure, `position` is the byte offset of the offending token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl QueryParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+
+/// Token of the query string, `text` without prefix nor quotes
+struct Token {
+    position: usize,
+    prefix: Option<char>,
+    text: String,
+    quoted: bool,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, QueryParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let prefix = match c {
+            '+' | '-' => {
+                chars.next();
+                Some(c)
+            },
+            _ => None,
+        };
+
+        let mut text = String::new();
+        let quoted = matches!(chars.peek(), Some((_, '"')));
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(QueryParseError::new(position, "Unterminated quote")),
+                }
+            }
+            if let Some(&(_, c)) = chars.peek() {
+                if !c.is_whitespace() {
+                    return Err(QueryParseError::new(position, "Expected a space after closing quote"));
+                }
+            }
+        } else {
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+        }
+
+        if text.trim().is_empty() {
+            let message = match prefix {
+                Some(p) => format!("Expected a tag after '{p}'"),
+                None => String::from("Empty quoted tag"),
+            };
+            return Err(QueryParseError::new(position, message));
+        }
+
+        tokens.push(Token { position, prefix, text, quoted });
+    }
+
+    Ok(tokens)
+}
+
+impl FromStr for SearchQuery {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut q = Self::default();
+        let tokens = tokenize(s)?;
+        let combined = tokens.len() > 1;
+
+        for token in tokens {
+            let position = token.position;
+
+            // Get parameters key:value, unknown keys are plain tags (ex. re:zero)
+            let param = match (token.prefix, token.quoted) {
+                (None, false) => token.text.split_once(':')
+                    .filter(|(key, _)| matches!(*key, "id" | "type" | "like")),
+                _ => None,
+            };
+
+            if let Some((key, value)) = param {
+                match key {
+                    "id" => {
+                        // Exclusive parameter, id is a tag number
+                        if combined {
+                            return Err(QueryParseError::new(position, "id: can not be combined with other terms"));
+                        }
+                        let id = value.parse::<u64>()
+                            .map_err(|_| QueryParseError::new(position, format!("Invalid tag id '{value}' - expected a number")))?;
+                        q.id = Some(id);
+                    },
+                    "type" => {
+                        let filetype = match value.to_ascii_lowercase().as_str() {
+                            "png" => FileType::Png,
+                            "jpg" | "jpeg" => FileType::Jpg,
+                            _ => return Err(QueryParseError::new(position, format!("Invalid file type '{value}' - only accept png or jpg"))),
+                        };
+                        if q.filetype.replace(filetype).is_some() {
+                            return Err(QueryParseError::new(position, "type: given more than once"));
+                        }
+                    },
+                    _ => {
+                        // Wallpaper ID, length 6 alphanumerical as of now
+                        if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric()) {
+                            return Err(QueryParseError::new(position, format!("Invalid wallpaper id '{value}'")));
+                        }
+                        if q.like.replace(String::from(value)).is_some() {
+                            return Err(QueryParseError::new(position, "like: given more than once"));
+                        }
+                    },
+                }
+
+                continue;
+            }
+
+            // Get username if any
+            if let (None, false, Some(username)) = (token.prefix, token.quoted, token.text.strip_prefix('@')) {
+                if username.is_empty() {
+                    return Err(QueryParseError::new(position, "Expected a username after '@'"));
+                }
+                if q.username.replace(String::from(username)).is_some() {
+                    return Err(QueryParseError::new(position, "Only one @username allowed"));
+                }
+                continue;
+            }
+
+            let kind = match token.prefix {
+                Some('+') => TermKind::Required,
+                Some('-') => TermKind::Excluded,
+                _ => TermKind::Fuzzy,
+            };
+            q.terms.push(Term { kind, text: token.text });
+        }
+
+        Ok(q)
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.kind {
+            TermKind::Fuzzy => "",
+            TermKind::Required => "+",
+            TermKind::Excluded => "-",
+        };
+
+        let needs_quotes = self.text.chars().any(char::is_whitespace)
+            // Would be read back as a prefix or parameter
+            || (self.kind == TermKind::Fuzzy && (self.text.starts_with(['+', '-', '@', '"'])
+                || self.text.split_once(':').map(|(k, _)| matches!(k, "id" | "type" | "like")).unwrap_or(false)));
+
+        if needs_quotes {
+            write!(f, "{prefix}\"{}\"", self.text)
+        } else {
+            write!(f, "{prefix}{}", self.text)
+        }
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(id) = &self.id {
+            return write!(f, "id:{id}");
+        }
+
+        let mut query: Vec<String> = self.terms.iter()
+            .map(|t| t.to_string())
+            .collect();
+
+        if let Some(username) = &self.username {
+            query.push(format!("@{username}"));
+        }
+        if let Some(ft) = &self.filetype {
+            query.push(format!("type:{ft}"));
+        }
+        if let Some(w) = &self.like {
+            query.push(format!("like:{w}"));
+        }
+
+        write!(f, "{}", query.join(" "))
+    }
+}
+
+/// Serialized in its canonical form
+impl Serialize for SearchQuery {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchQuery {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(kind: TermKind, text: &str) -> Term {
+        Term { kind, text: String::from(text) }
+    }
+
+    #[test]
+    fn parses_term_kinds() {
+        let q: SearchQuery = "anime +city -mountain".parse().unwrap();
+
+        assert_eq!(q.terms, vec![
+            term(TermKind::Fuzzy, "anime"),
+            term(TermKind::Required, "city"),
+            term(TermKind::Excluded, "mountain"),
+        ]);
+    }
+
+    #[test]
+    fn parses_quoted_phrases() {
+        let q: SearchQuery = r#"+"blue sky" -"night city"  cat"#.parse().unwrap();
+
+        assert_eq!(q.terms, vec![
+            term(TermKind::Required, "blue sky"),
+            term(TermKind::Excluded, "night city"),
+            term(TermKind::Fuzzy, "cat"),
+        ]);
+    }
+
+    #[test]
+    fn parses_parameters() {
+        let q: SearchQuery = "cat @someone type:jpeg like:94x38z re:zero".parse().unwrap();
+
+        assert_eq!(q.username.as_deref(), Some("someone"));
+        assert_eq!(q.filetype, Some(FileType::Jpg));
+        assert_eq!(q.like.as_deref(), Some("94x38z"));
+        assert_eq!(q.terms, vec![term(TermKind::Fuzzy, "cat"), term(TermKind::Fuzzy, "re:zero")]);
+    }
+
+    #[test]
+    fn canonical_round_trip() {
+        let input = r#"like:94x38z  -dog @someone +"blue sky" cat type:png"#;
+        let q: SearchQuery = input.parse().unwrap();
+        let canonical = q.to_string();
+
+        assert_eq!(canonical, r#"-dog +"blue sky" cat @someone type:png like:94x38z"#);
+        assert_eq!(canonical.parse::<SearchQuery>().unwrap(), q);
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let err = r#"cat +"blue sky"#.parse::<SearchQuery>().unwrap_err();
+        assert_eq!(err.position, 4);
+
+        let err = "cat id:12".parse::<SearchQuery>().unwrap_err();
+        assert_eq!(err.position, 4);
+
+        let err = "cat type:gif".parse::<SearchQuery>().unwrap_err();
+        assert!(err.message.contains("gif"));
+
+        assert!("cat + dog".parse::<SearchQuery>().is_err());
+    }
+}
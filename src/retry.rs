@@ -0,0 +1,240 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::api::WallhavenClientError;
+
+
+/// Requests per minute allowed by the api
+pub const DEFAULT_RATE_LIMIT: u32 = 45;
+
+
+/// Token bucket shared by every api request of a client
+///
+/// Holds up to `per_minute` tokens, refilled continuously,
+/// so short bursts go through and longer runs are paced.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    per_minute: u32,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// `per_minute` of 0 disables the limit
+    pub(crate) fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            state: Mutex::new(Bucket {
+                tokens: per_minute as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a request is allowed
+    pub(crate) async fn acquire(&self) {
+        if self.per_minute == 0 {
+            return;
+        }
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+
+        loop {
+            let wait = {
+                let mut bucket = self.state.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * per_sec).min(capacity);
+                bucket.last = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+
+/// How many times and how long to wait before retrying a failed request
+///
+/// Rate limited (429), server errors (5xx) and connection errors are retried
+/// with exponential backoff, honoring `Retry-After` when the server sends it.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_retries(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (0 based) after `error`,
+    /// `None` if it shouldn't be retried
+    fn delay(&self, attempt: u32, error: &WallhavenClientError) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+
+        let backoff = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        match error {
+            WallhavenClientError::RateLimited { retry_after: Some(secs) } => Some(Duration::from_secs(*secs)),
+            WallhavenClientError::RateLimited { retry_after: None } => Some(backoff),
+            WallhavenClientError::Api { status, .. } if *status >= 500 => Some(backoff),
+            WallhavenClientError::RequestError(_) => Some(backoff),
+            _ => None,
+        }
+    }
+
+    /// Run `f` until it succeeds, fails with a non retryable error or runs out of retries
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T, WallhavenClientError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, WallhavenClientError>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) => {
+                    let Some(delay) = self.delay(attempt, &e) else {
+                        return Err(e);
+                    };
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn backoff_grows_and_caps() {
+        let e = WallhavenClientError::RequestError(String::from("reset"));
+
+        let delays: Vec<_> = (0..8).map(|n| policy().delay(n, &e).unwrap().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(policy().delay(10, &e), None);
+    }
+
+    #[test]
+    fn retry_after_takes_precedence() {
+        let e = WallhavenClientError::RateLimited { retry_after: Some(7) };
+        assert_eq!(policy().delay(5, &e), Some(Duration::from_secs(7)));
+
+        let e = WallhavenClientError::RateLimited { retry_after: None };
+        assert_eq!(policy().delay(2, &e), Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let retried = |e: WallhavenClientError| policy().delay(0, &e).is_some();
+
+        assert!(retried(WallhavenClientError::RateLimited { retry_after: None }));
+        assert!(retried(WallhavenClientError::Api { status: 503, message: String::new() }));
+        assert!(retried(WallhavenClientError::RequestError(String::new())));
+        assert!(!retried(WallhavenClientError::Api { status: 400, message: String::new() }));
+        assert!(!retried(WallhavenClientError::Unauthorized));
+        assert!(!retried(WallhavenClientError::NotFound));
+        assert!(!retried(WallhavenClientError::DecodeError(String::new())));
+        assert!(!retried(WallhavenClientError::WriteError(String::new())));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_until_success_or_out_of_retries() {
+        let policy = RetryPolicy::with_retries(2);
+
+        let mut calls = 0;
+        let res = policy.run(|| {
+            calls += 1;
+            let n = calls;
+            async move {
+                if n < 3 { Err(WallhavenClientError::RequestError(String::new())) } else { Ok(n) }
+            }
+        }).await;
+        assert_eq!(res.unwrap(), 3);
+
+        let mut calls = 0;
+        let res: Result<(), _> = policy.run(|| {
+            calls += 1;
+            async { Err(WallhavenClientError::NotFound) }
+        }).await;
+        assert!(matches!(res, Err(WallhavenClientError::NotFound)));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_allows_bursts_then_paces() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(10));
+
+        // Refilled at one token a second
+        limiter.acquire().await;
+        limiter.acquire().await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(1990) && elapsed < Duration::from_millis(2100), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_of_zero_is_unlimited() {
+        let limiter = RateLimiter::new(0);
+        let start = Instant::now();
+
+        for _ in 0..1000 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
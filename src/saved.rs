@@ -0,0 +1,293 @@
+//! Named saved searches
+//!
+//! `~/.config/wallhaven/saved.toml` keeps the flags given to `saved add`,
+//! one table per search keyed like the config file (long flag names).
+//!
+//! ```toml
+//! [toplist-4k]
+//! query = "+landscape -city"
+//! purity = "sfw"
+//! sorting = "TOPLIST"
+//! atleast = "3840x2160"
+//! ratios = ["16x9", "ultrawide"]
+//! path = "/home/user/wallpapers/toplist"
+//! ```
+//!
+//! Running one replays its flags under the `search` command, as defaults
+//! for the flags given on the command line, ahead of the config ones.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+use clap::{CommandFactory, Parser};
+
+use crate::args::{CLIArgs, CLICommands, SavedCommands};
+use crate::config::{self, Config};
+
+
+/// Outcome of a `saved` command
+pub enum Saved {
+    /// Text to print
+    Output(String),
+    /// Arguments of the search to run
+    Search(Box<CLIArgs>),
+}
+
+/// Why a `saved` command failed
+#[derive(Debug)]
+pub enum Error {
+    /// Flags that aren't valid search flags, reported like any usage error
+    Usage(clap::Error),
+    Other(String),
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(e) => write!(f, "{}", e),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Run `command`, `argv` and `config` are needed to replay a search
+pub fn execute(command: &SavedCommands, argv: &[OsString], config: &Config) -> Result<Saved, Error> {
+    let mut saved = SavedSearches::load()?;
+
+    let output = match command {
+        SavedCommands::Add { name, flags } => {
+            saved.add(name, flags)?;
+            saved.save()?;
+            format!("Saved search '{}'", name)
+        },
+        SavedCommands::List => saved.searches.keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        SavedCommands::Show { name } => {
+            let mut table = toml::Table::new();
+            table.insert(name.clone(), toml::Value::Table(saved.get(name)?.clone()));
+
+            toml::to_string(&table).map_err(|e| e.to_string())?
+        },
+        SavedCommands::Rm { name } => {
+            saved.get(name)?;
+            saved.searches.remove(name);
+            saved.save()?;
+            format!("Removed saved search '{}'", name)
+        },
+        SavedCommands::Run { name, .. } => {
+            return Ok(Saved::Search(Box::new(saved.run(name, argv, config)?)));
+        },
+    };
+
+    Ok(Saved::Output(output))
+}
+
+
+struct SavedSearches {
+    path: PathBuf,
+    searches: toml::Table,
+}
+
+impl SavedSearches {
+    fn load() -> Result<Self, String> {
+        let path = config::config_dir()
+            .ok_or("Can't find the config directory, set HOME or XDG_CONFIG_HOME")?
+            .join("saved.toml");
+
+        let searches = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| format!("Invalid saved searches {} - {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(format!("Failed to read saved searches {} - {}", path.display(), e)),
+        };
+
+        Ok(Self { path, searches })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let content = toml::to_string(&self.searches)
+            .map_err(|e| e.to_string())?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {} - {}", dir.display(), e))?;
+        }
+        config::write_file(&self.path, &content)
+            .map_err(|e| format!("Failed to write saved searches {} - {}", self.path.display(), e))
+    }
+
+    fn get(&self, name: &str) -> Result<&toml::Table, String> {
+        match self.searches.get(name) {
+            Some(toml::Value::Table(t)) => Ok(t),
+            Some(_) => Err(format!("Saved search '{}' is not a table", name)),
+            None => Err(format!("No saved search named '{}'", name)),
+        }
+    }
+
+    /// Arguments of the search `name`, `argv` is the `saved run` command line
+    /// which flags override the saved ones, ahead of the `config` ones
+    fn run(&self, name: &str, argv: &[OsString], config: &Config) -> Result<CLIArgs, Error> {
+        // Same command line, `saved` replaced by `search` and `run <name>` left out,
+        // global flags in between are kept
+        let cmd = CLIArgs::command();
+        let not_run = || format!("Expected `saved run {}`", name);
+        let saved_at = config::subcommand_index(&cmd, argv).ok_or_else(not_run)?;
+        let run_at = saved_at + config::subcommand_index(&cmd, &argv[saved_at..]).ok_or_else(not_run)?;
+        let name_at = run_at + config::subcommand_index(&cmd, &argv[run_at..]).ok_or_else(not_run)?;
+        let search_argv = argv.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != run_at && *i != name_at)
+            .map(|(i, token)| if i == saved_at { OsString::from("search") } else { token.clone() })
+            .collect();
+
+        let values = Config::from_values(self.get(name)?.clone())
+            .map_err(|e| format!("Saved search '{}' - {}", name, e))?;
+        let search_argv = config.apply(values.apply(search_argv));
+
+        CLIArgs::try_parse_from(search_argv).map_err(Error::Usage)
+    }
+
+    /// Validate `flags` as search flags and keep the given ones under `name`
+    fn add(&mut self, name: &str, flags: &[String]) -> Result<(), Error> {
+        let mut argv = vec![OsString::from("wallhaven"), OsString::from("search")];
+        argv.extend(flags.iter().map(OsString::from));
+
+        let args = CLIArgs::try_parse_from(&argv).map_err(Error::Usage)?;
+        let CLICommands::Search(mut search) = args.commands else {
+            unreachable!("parsed as a search");
+        };
+
+        // Relative paths would depend on where it runs, ~/ is expanded when run
+        if let Some(path) = search.download.path.as_mut().filter(|p| !p.starts_with("~/")) {
+            let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+            *path = cwd.join(&*path).to_string_lossy().into_owned();
+        }
+
+        let cmd = CLIArgs::command();
+        let sub = cmd.find_subcommand("search").expect("search command");
+        let given = config::given_ids(&cmd, sub, &argv);
+
+        let serde_json::Value::Object(values) = serde_json::to_value(&search).map_err(|e| e.to_string())? else {
+            unreachable!("search arguments are a struct");
+        };
+
+        // Serialized fields are named like the config keys, given ones by arg id
+        let is_given = |key: &str| config::find_arg(sub, &config::flag_name(key))
+            .is_some_and(|arg| given.contains(arg.get_id()));
+
+        let mut table = toml::Table::new();
+        for (key, value) in values {
+            if !is_given(&key) || value.is_null() {
+                continue;
+            }
+            let value = toml::Value::try_from(value).map_err(|e| e.to_string())?;
+            table.insert(key, value);
+        }
+
+        self.searches.insert(String::from(name), toml::Value::Table(table));
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn searches() -> SavedSearches {
+        SavedSearches {
+            path: PathBuf::from("saved.toml"),
+            searches: toml::Table::new(),
+        }
+    }
+
+    fn argv(tokens: &[&str]) -> Vec<OsString> {
+        tokens.iter().map(OsString::from).collect()
+    }
+
+    /// Search arguments as saved, through serde
+    fn search_values(args: CLIArgs) -> serde_json::Value {
+        let CLICommands::Search(search) = args.commands else {
+            panic!("not a search");
+        };
+        serde_json::to_value(&search).unwrap()
+    }
+
+    fn round_trip(flags: &[&str]) -> toml::Table {
+        let flags: Vec<String> = flags.iter().map(|f| f.to_string()).collect();
+        let mut saved = searches();
+        saved.add("t", &flags).unwrap();
+
+        let run = saved.run("t", &argv(&["wallhaven", "saved", "run", "t"]), &Config::default()).unwrap();
+        let mut direct = argv(&["wallhaven", "search"]);
+        direct.extend(flags.iter().map(OsString::from));
+        assert_eq!(search_values(run), search_values(CLIArgs::parse_from(direct)));
+
+        saved.get("t").unwrap().clone()
+    }
+
+    #[test]
+    fn round_trips_every_search_flag() {
+        let mut table = round_trip(&[
+            "--jobs", "3", "--layout", "{purity}/{year}", "--name-template", "{id}.{ext}",
+            "--on-exists", "rename", "--path", "/w", "--sidecar", "--variant", "thumb-small",
+            "--colors", "teal,#ff6600", "--query", "+cat -dog", "--categories", "anime",
+            "--filter", "favorites>100", "--limit", "20", "--purity", "sfw,sketchy",
+            "--page", "2", "--pages", "3", "--seed", "abc123",
+            "--order", "ASC", "--sorting", "RANDOM", "--toprange", "1W", "--atleast", "1920x1080",
+            "--resolutions", "2560x1440,3840x2160", "--ratios", "16x9,ultrawide",
+        ]);
+        // Conflict with --pages and --limit
+        table.extend(round_trip(&["-q", "cat", "--all", "--since-last-run", "feed"]));
+
+        let cmd = CLIArgs::command();
+        let sub = cmd.find_subcommand("search").unwrap();
+        for arg in sub.get_arguments().filter(|a| !["help", "version"].contains(&a.get_id())) {
+            let long = arg.get_long().unwrap();
+            assert!(table.contains_key(&long.replace('-', "_")), "--{long} wasn't saved");
+        }
+    }
+
+    #[test]
+    fn run_keeps_global_flags() {
+        let mut saved = searches();
+        saved.add("t", &["-q".into(), "cat".into()]).unwrap();
+
+        let run = saved.run("t", &argv(&["wallhaven", "--retries", "1", "saved", "--rate-limit", "5", "run", "t", "--pages", "2"]), &Config::default()).unwrap();
+        assert_eq!(run.retries, 1);
+        assert_eq!(run.rate_limit, 5);
+        assert_eq!(search_values(run)["pages"], 2);
+    }
+
+    #[test]
+    fn reports_invalid_flags() {
+        let mut saved = searches();
+        assert!(matches!(saved.add("t", &["--pages".into(), "0".into()]), Err(Error::Usage(_))));
+        assert!(saved.searches.is_empty());
+
+        saved.add("t", &["-q".into(), "cat".into(), "--all".into()]).unwrap();
+        let run = saved.run("t", &argv(&["wallhaven", "saved", "run", "t", "--jobs", "x"]), &Config::default());
+        assert!(matches!(run, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn run_overrides_conflicting_flags() {
+        let mut saved = searches();
+        saved.add("t", &["-q".into(), "cat".into(), "--all".into(), "--jobs".into(), "2".into()]).unwrap();
+
+        let run = saved.run("t", &argv(&["wallhaven", "saved", "run", "t", "--pages", "2"]), &Config::default()).unwrap();
+        let values = search_values(run);
+        assert_eq!(values["pages"], 2);
+        assert_eq!(values["all"], false);
+        assert_eq!(values["jobs"], 2);
+    }
+}
@@ -0,0 +1,66 @@
+//! State kept between runs
+//!
+//! `~/.local/state/wallhaven/state.toml` records the newest wallpaper
+//! seen by each `--since-last-run` key.
+//!
+//! ```toml
+//! [since.latest-anime]
+//! id = "94x38z"
+//! created_at = "2024-03-10 12:30:00"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use wallhaven::SeenMark;
+
+use crate::config;
+
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    since: BTreeMap<String, SeenMark>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl State {
+    pub fn load() -> Result<Self, String> {
+        let path = config::state_dir()
+            .ok_or("Can't find the state directory, set HOME or XDG_STATE_HOME")?
+            .join("state.toml");
+
+        let mut state: State = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| format!("Invalid state {} - {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(format!("Failed to read state {} - {}", path.display(), e)),
+        };
+        state.path = path;
+
+        Ok(state)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = toml::to_string(self)
+            .map_err(|e| e.to_string())?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {} - {}", dir.display(), e))?;
+        }
+        config::write_file(&self.path, &content)
+            .map_err(|e| format!("Failed to write state {} - {}", self.path.display(), e))
+    }
+
+    /// Newest wallpaper seen by the last run of `key`
+    pub fn since(&self, key: &str) -> Option<&SeenMark> {
+        self.since.get(key)
+    }
+
+    pub fn set_since(&mut self, key: &str, mark: SeenMark) {
+        self.since.insert(String::from(key), mark);
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::api::Wallpaper;
+use crate::download::Variant;
+
+
+/// File name built from wallpaper fields, ex. `{id}_{resolution}_{category}.{ext}`
+///
+///    {field} - any wallpaper field: id, url, short_url, views, favorites, source,
+///              purity, category, dimension_x (width), dimension_y (height),
+///              resolution, ratio, file_size, file_type, created_at, colors, path
+///    {ext} - extension of the downloaded image (jpg/png)
+///    {variant} - full, thumb-small, thumb-large or thumb-original
+///    {created_at:%Y%m%d} - date format, %Y %y %m %d %H %M %S
+///    {year} {month} {day} - parts of created_at
+///    {{ and }} - literal braces
+///
+/// Values are sanitized so they can't add directories nor unsafe characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Id,
+    Url,
+    ShortUrl,
+    Views,
+    Favorites,
+    Source,
+    Purity,
+    Category,
+    Width,
+    Height,
+    Resolution,
+    Ratio,
+    FileSize,
+    FileType,
+    CreatedAt,
+    Year,
+    Month,
+    Day,
+    Colors,
+    Path,
+    Ext,
+    Variant,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "id" => Self::Id,
+            "url" => Self::Url,
+            "short_url" => Self::ShortUrl,
+            "views" => Self::Views,
+            "favorites" => Self::Favorites,
+            "source" => Self::Source,
+            "purity" => Self::Purity,
+            "category" => Self::Category,
+            "dimension_x" | "width" => Self::Width,
+            "dimension_y" | "height" => Self::Height,
+            "resolution" => Self::Resolution,
+            "ratio" => Self::Ratio,
+            "file_size" => Self::FileSize,
+            "file_type" => Self::FileType,
+            "created_at" => Self::CreatedAt,
+            "year" => Self::Year,
+            "month" => Self::Month,
+            "day" => Self::Day,
+            "colors" => Self::Colors,
+            "path" => Self::Path,
+            "ext" => Self::Ext,
+            "variant" => Self::Variant,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    fn value(&self, w: &Wallpaper, variant: Variant, format: Option<&str>) -> String {
+        match self {
+            Self::Id => w.id.clone(),
+            Self::Url => w.url.clone(),
+            Self::ShortUrl => w.short_url.clone(),
+            Self::Views => w.views.to_string(),
+            Self::Favorites => w.favorites.to_string(),
+            Self::Source => w.source.clone(),
+            Self::Purity => w.purity.to_string(),
+            Self::Category => w.category.to_string(),
+            Self::Width => w.dimension_x.to_string(),
+            Self::Height => w.dimension_y.to_string(),
+            Self::Resolution => w.resolution.to_string(),
+            // Same decimal form as the api
+            Self::Ratio => format!("{:.2}", w.ratio.value()),
+            Self::FileSize => w.file_size.to_string(),
+            Self::FileType => w.file_type.clone(),
+            Self::CreatedAt => match format {
+                Some(f) => format_date(&w.created_at, f),
+                None => w.created_at.clone(),
+            },
+            Self::Year => format_date(&w.created_at, "%Y"),
+            Self::Month => format_date(&w.created_at, "%m"),
+            Self::Day => format_date(&w.created_at, "%d"),
+            Self::Colors => w.colors.iter()
+                .map(|c| c.trim_start_matches('#'))
+                .collect::<Vec<_>>()
+                .join("-"),
+            Self::Path => w.path.clone(),
+            Self::Ext => extension(w, variant),
+            Self::Variant => variant.to_string(),
+        }
+    }
+}
+
+impl Template {
+    /// Default file name, `wallhaven-{id}.{ext}` like the image urls,
+    /// thumbnails get `-{variant}` so they don't take the name of the full image
+    pub fn default_name(variant: Variant) -> Self {
+        let name = match variant {
+            Variant::Full => "wallhaven-{id}.{ext}",
+            _ => "wallhaven-{id}-{variant}.{ext}",
+        };
+        name.parse().expect("valid default name")
+    }
+
+    pub fn render(&self, wallpaper: &Wallpaper) -> String {
+        self.render_variant(wallpaper, Variant::Full)
+    }
+
+    /// Render for the `variant` image of `wallpaper`, which sets `{ext}` and `{variant}`
+    pub fn render_variant(&self, wallpaper: &Wallpaper, variant: Variant) -> String {
+        self.parts.iter()
+            .map(|part| match part {
+                Part::Literal(s) => s.clone(),
+                Part::Field(field, format) => sanitize(&field.value(wallpaper, variant, format.as_deref())),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '}' => return Err(String::from("Unmatched '}' - use }} for a literal brace")),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("Unclosed placeholder {{{placeholder}")),
+                        }
+                    }
+
+                    let (name, format) = match placeholder.split_once(':') {
+                        Some((name, format)) => (name, Some(String::from(format))),
+                        None => (placeholder.as_str(), None),
+                    };
+                    let field = Field::from_name(name.trim())
+                        .ok_or(format!("Unknown placeholder {{{name}}}"))?;
+                    if format.is_some() && field != Field::CreatedAt {
+                        return Err(format!("Only created_at takes a format, not {{{name}}}"));
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field, format));
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self {
+            source: String::from(s),
+            parts,
+        })
+    }
+}
+
+impl Serialize for Template {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+
+/// Extension of the image file, from its url or else its mime type
+fn extension(w: &Wallpaper, variant: Variant) -> String {
+    let name = variant.url(w).rsplit('/').next().unwrap_or_default();
+
+    match name.rsplit_once('.') {
+        Some((_, ext)) if !ext.is_empty() => String::from(ext),
+        _ => match w.file_type.as_str() {
+            "image/png" if variant == Variant::Full => String::from("png"),
+            _ => String::from("jpg"),
+        },
+    }
+}
+
+/// `created_at` (`YYYY-MM-DD HH:MM:SS`) in a strftime like format
+fn format_date(date: &str, format: &str) -> String {
+    let field = |range: std::ops::Range<usize>| date.get(range).unwrap_or_default();
+
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(field(0..4)),
+            Some('y') => out.push_str(field(2..4)),
+            Some('m') => out.push_str(field(5..7)),
+            Some('d') => out.push_str(field(8..10)),
+            Some('H') => out.push_str(field(11..13)),
+            Some('M') => out.push_str(field(14..16)),
+            Some('S') => out.push_str(field(17..19)),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            },
+            None => out.push('%'),
+        }
+    }
+
+    out
+}
+
+/// Replace path separators, characters not allowed in file names and control characters
+pub fn sanitize(s: &str) -> String {
+    let s: String = s.chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+
+    // Hidden files or names ending in a dot are trouble on some systems
+    let s = s.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if s.is_empty() {
+        String::from("_")
+    } else {
+        String::from(s)
+    }
+}
+
+
+/// Paths of downloaded wallpapers, from a name [`Template`]
+/// and optionally a directory layout one, ex. `{purity}/{category}`
+///
+/// Two wallpapers rendering to the same path in a run is a collision,
+/// the later one gets its id appended (`<name>-<id>.<ext>`).
+#[derive(Debug)]
+pub struct FileNamer {
+    name: Template,
+    layout: Option<Template>,
+    variant: Variant,
+    /// Paths given so far, with the wallpaper id they were given to
+    taken: HashMap<PathBuf, String>,
+}
+
+impl FileNamer {
+    pub fn new(name: Template) -> Self {
+        Self {
+            name,
+            layout: None,
+            variant: Variant::Full,
+            taken: HashMap::new(),
+        }
+    }
+
+    /// Save wallpapers in the subdirectories given by `layout`, `/` separated
+    pub fn with_layout(mut self, layout: Template) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Name files after the `variant` image
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Path of `wallpaper` under `dir`, and the id of the wallpaper it collided with if any
+    pub fn path(&mut self, dir: &Path, wallpaper: &Wallpaper) -> (PathBuf, Option<String>) {
+        let dir = match &self.layout {
+            // Empty parts are dropped, `..` and such are sanitized away
+            Some(layout) => layout.render_variant(wallpaper, self.variant)
+                .split('/')
+                .filter(|part| !part.trim().is_empty())
+                .fold(dir.to_path_buf(), |dir, part| dir.join(sanitize(part))),
+            None => dir.to_path_buf(),
+        };
+        let name = sanitize(&self.name.render_variant(wallpaper, self.variant));
+        let mut path = dir.join(&name);
+        let mut collided = None;
+
+        if let Some(other) = self.taken.get(&path).filter(|id| **id != wallpaper.id) {
+            collided = Some(other.clone());
+
+            let name = Path::new(&name);
+            let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+            let ext = name.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            path = dir.join(format!("{stem}-{}{ext}", wallpaper.id));
+        }
+
+        self.taken.insert(path.clone(), wallpaper.id.clone());
+        (path, collided)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::wallpaper;
+
+    fn render(template: &str, w: &Wallpaper) -> String {
+        template.parse::<Template>().unwrap().render(w)
+    }
+
+    #[test]
+    fn renders_fields() {
+        let w = wallpaper("94x38z");
+
+        assert_eq!(render("{id}_{resolution}_{category}_{purity}.{ext}", &w), "94x38z_2560x1440_anime_sfw.png");
+        assert_eq!(render("{created_at:%Y%m%d}-{id}.{ext}", &w), "20240310-94x38z.png");
+        assert_eq!(render("{ratio} {colors} {{id}}", &w), "1.78 0066cc-000000 {id}");
+    }
+
+    #[test]
+    fn default_names_tell_variants_apart() {
+        let w = wallpaper("94x38z");
+
+        assert_eq!(Template::default_name(Variant::Full).render(&w), "wallhaven-94x38z.png");
+        assert_eq!(Template::default_name(Variant::ThumbSmall).render_variant(&w, Variant::ThumbSmall), "wallhaven-94x38z-thumb-small.jpg");
+    }
+
+    #[test]
+    fn renders_variants() {
+        let w = wallpaper("94x38z");
+        let template: Template = "{variant}/{id}.{ext}".parse().unwrap();
+
+        assert_eq!(template.render(&w), "full/94x38z.png");
+        assert_eq!(template.render_variant(&w, Variant::ThumbLarge), "thumb-large/94x38z.jpg");
+    }
+
+    #[test]
+    fn sanitizes_values() {
+        let w = wallpaper("94x38z");
+
+        assert_eq!(render("{source}", &w), "https___example.com_art station_1");
+        assert_eq!(render("{created_at}", &w), "2024-03-10 12_30_00");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        assert!("{nope}.jpg".parse::<Template>().is_err());
+        assert!("{id".parse::<Template>().is_err());
+        assert!("{id}}".parse::<Template>().is_err());
+        assert!("{views:%Y}".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn detects_collisions() {
+        let mut namer = FileNamer::new("{category}.{ext}".parse().unwrap());
+        let dir = Path::new("walls");
+
+        assert_eq!(namer.path(dir, &wallpaper("aaaaaa")), (dir.join("anime.png"), None));
+        assert_eq!(namer.path(dir, &wallpaper("aaaaaa")), (dir.join("anime.png"), None));
+        assert_eq!(namer.path(dir, &wallpaper("bbbbbb")), (dir.join("anime-bbbbbb.png"), Some(String::from("aaaaaa"))));
+    }
+
+    #[test]
+    fn lays_out_directories() {
+        let dir = Path::new("walls");
+
+        let mut namer = FileNamer::new("{id}.{ext}".parse().unwrap())
+            .with_layout("{purity}/{category}/{ratio}".parse().unwrap());
+        assert_eq!(namer.path(dir, &wallpaper("aaaaaa")).0, dir.join("sfw/anime/1.78/aaaaaa.png"));
+
+        let mut namer = FileNamer::new("{id}.{ext}".parse().unwrap())
+            .with_layout("/{year}//{month}/../{source}".parse().unwrap());
+        assert_eq!(namer.path(dir, &wallpaper("aaaaaa")).0, dir.join("2024/03/_/https___example.com_art station_1/aaaaaa.png"));
+    }
+}